@@ -0,0 +1,445 @@
+// src/candidate_filter.rs
+
+use crate::metadata_retriever::get_video_metadata;
+use rayon::prelude::*;
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// Durations probed with ffprobe, keyed by path. `None` records that the duration could
+/// not be determined, so files ffprobe cannot read are not probed again on every pick.
+pub type DurationCache = HashMap<PathBuf, Option<f64>>;
+
+/// Restrictions on which scanned videos are candidates for a pick.
+/// Applied to the scanned list before weighting; every bound is optional.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CandidateFilter {
+    /// Minimum file size in bytes.
+    pub min_size: Option<u64>,
+    /// Maximum file size in bytes.
+    pub max_size: Option<u64>,
+    /// Only files modified at most this long ago.
+    pub modified_within: Option<Duration>,
+    /// Minimum video duration (as reported by ffprobe).
+    pub min_duration: Option<Duration>,
+    /// Maximum video duration (as reported by ffprobe).
+    pub max_duration: Option<Duration>,
+}
+
+/// The candidates of the last filter pass, reused until the scanned videos or the filter
+/// change, so files are not inspected again on every pick. Only the `modified_within`
+/// bound is checked again on each use, against the modification times read in the pass.
+#[derive(Debug, Default)]
+pub struct CandidateCache {
+    /// The filter the candidates were selected with.
+    filter: CandidateFilter,
+    /// The videos the candidates were selected from.
+    videos: Vec<PathBuf>,
+    /// The videos that passed the size and duration bounds, with their modification times.
+    candidates: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+/// Parses a file size such as `700M`, `1.5G` or `1048576`.
+/// Suffixes `K`, `M`, `G` and `T` are powers of 1024 (as in `du`), optionally
+/// followed by `B` or `iB`; a bare number is a byte count.
+///
+/// # Errors
+///
+/// Returns a description of the problem if the size cannot be parsed.
+pub fn parse_size(input: &str) -> Result<u64, String> {
+    let trimmed = input.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let number: f64 = number.parse().map_err(|_| {
+        format!(
+            "invalid size '{}': expected a number like 700M or 1.5G",
+            input
+        )
+    })?;
+    let unit = unit.trim().to_ascii_lowercase();
+    let unit = unit
+        .strip_suffix("ib")
+        .or_else(|| unit.strip_suffix('b'))
+        .unwrap_or(&unit);
+    let multiplier: u64 = match unit {
+        "" => 1,
+        "k" => 1 << 10,
+        "m" => 1 << 20,
+        "g" => 1 << 30,
+        "t" => 1 << 40,
+        _ => {
+            return Err(format!(
+                "invalid size unit in '{}': use K, M, G or T",
+                input
+            ))
+        }
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Parses a duration such as `30d`, `1h30m` or `90s`.
+///
+/// # Errors
+///
+/// Returns a description of the problem if the duration cannot be parsed.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    humantime::parse_duration(input.trim())
+        .map_err(|e| format!("invalid duration '{}': {} (e.g. 30d, 1h30m, 90s)", input, e))
+}
+
+/// Formats a byte count for display, e.g. `1.5 GiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+impl CandidateFilter {
+    /// Returns true if no bound is set, so every scanned video is a candidate.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns true if the filter needs video durations, which means running ffprobe.
+    pub fn needs_duration(&self) -> bool {
+        self.min_duration.is_some() || self.max_duration.is_some()
+    }
+
+    /// Returns the videos from `videos` that pass the size and duration bounds, with their
+    /// modification times (read only if `modified_within` is set). The time-based bound is
+    /// left to the caller, as its outcome changes as time passes.
+    fn inspect(
+        &self,
+        videos: &[PathBuf],
+        durations: &mut DurationCache,
+    ) -> Vec<(PathBuf, Option<SystemTime>)> {
+        let candidates: Vec<(PathBuf, Option<SystemTime>)> = videos
+            .iter()
+            .filter_map(|path| Some((path.clone(), self.inspect_file(path)?)))
+            .collect();
+        if !self.needs_duration() {
+            return candidates;
+        }
+
+        // Probe in parallel; ffprobe runs are slow compared to a stat.
+        let probed: Vec<(PathBuf, Option<f64>)> = candidates
+            .par_iter()
+            .filter(|(path, _)| !durations.contains_key(path))
+            .map(|(path, _)| {
+                let secs = match get_video_metadata(path) {
+                    Ok(metadata) => metadata.duration_secs,
+                    Err(e) => {
+                        log::debug!("Could not probe '{}': {}", path.display(), e);
+                        None
+                    }
+                };
+                (path.clone(), secs)
+            })
+            .collect();
+        durations.extend(probed);
+
+        candidates
+            .into_iter()
+            .filter(|(path, _)| {
+                durations
+                    .get(path)
+                    .copied()
+                    .flatten()
+                    .is_some_and(|secs| self.allows_duration(secs))
+            })
+            .collect()
+    }
+
+    /// Checks the size bounds against the file's metadata. Returns `None` if the file is
+    /// out of bounds or cannot be inspected, and its modification time otherwise.
+    fn inspect_file(&self, path: &Path) -> Option<Option<SystemTime>> {
+        if self.min_size.is_none() && self.max_size.is_none() && self.modified_within.is_none() {
+            return Some(None);
+        }
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                log::debug!("Could not read metadata of '{}': {}", path.display(), e);
+                return None;
+            }
+        };
+        let size = metadata.len();
+        if self.min_size.is_some_and(|min| size < min)
+            || self.max_size.is_some_and(|max| size > max)
+        {
+            return None;
+        }
+        Some(metadata.modified().ok())
+    }
+
+    /// Checks the modification time bound at `now`.
+    fn allows_modified(&self, modified: Option<SystemTime>, now: SystemTime) -> bool {
+        match self.modified_within {
+            Some(window) => modified.is_some_and(|modified| {
+                // Files with a timestamp in the future count as just modified.
+                now.duration_since(modified).unwrap_or_default() <= window
+            }),
+            None => true,
+        }
+    }
+
+    /// Checks the duration bounds against a duration in seconds.
+    fn allows_duration(&self, secs: f64) -> bool {
+        self.min_duration
+            .is_none_or(|min| secs >= min.as_secs_f64())
+            && self
+                .max_duration
+                .is_none_or(|max| secs <= max.as_secs_f64())
+    }
+}
+
+impl CandidateCache {
+    /// Returns the videos from `videos` that pass every bound of `filter` at `now`, in
+    /// their original order. The files are only inspected if the videos or the filter
+    /// changed since the last call. Files that cannot be inspected (or whose duration is
+    /// unknown while a duration bound is set) are left out.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - The active filter.
+    /// * `videos` - The scanned video paths.
+    /// * `now` - The time the `modified_within` bound is checked at.
+    /// * `durations` - Durations probed earlier in the session; missing ones are added.
+    pub fn get(
+        &mut self,
+        filter: &CandidateFilter,
+        videos: &[PathBuf],
+        now: SystemTime,
+        durations: &mut DurationCache,
+    ) -> Vec<PathBuf> {
+        if filter.is_empty() {
+            return videos.to_vec();
+        }
+        if self.filter != *filter || self.videos != videos {
+            self.candidates = filter.inspect(videos, durations);
+            self.filter = filter.clone();
+            self.videos = videos.to_vec();
+        }
+        self.candidates
+            .iter()
+            .filter(|(_, modified)| filter.allows_modified(*modified, now))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+}
+
+impl fmt::Display for CandidateFilter {
+    /// Describes the active bounds, e.g. `size ≥ 700.0 MiB, modified within 30days`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(min) = self.min_size {
+            parts.push(format!("size ≥ {}", format_size(min)));
+        }
+        if let Some(max) = self.max_size {
+            parts.push(format!("size ≤ {}", format_size(max)));
+        }
+        if let Some(window) = self.modified_within {
+            parts.push(format!(
+                "modified within {}",
+                humantime::format_duration(window)
+            ));
+        }
+        if let Some(min) = self.min_duration {
+            parts.push(format!("duration ≥ {}", humantime::format_duration(min)));
+        }
+        if let Some(max) = self.max_duration {
+            parts.push(format!("duration ≤ {}", humantime::format_duration(max)));
+        }
+        if parts.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use tempfile::tempdir;
+
+    /// Applies `filter` now, without a cache from earlier passes.
+    fn apply(
+        filter: &CandidateFilter,
+        videos: &[PathBuf],
+        durations: &mut DurationCache,
+    ) -> Vec<PathBuf> {
+        CandidateCache::default().get(filter, videos, SystemTime::now(), durations)
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1048576"), Ok(1_048_576));
+        assert_eq!(parse_size("700M"), Ok(700 << 20));
+        assert_eq!(parse_size("1.5G"), Ok(3 << 29));
+        assert_eq!(parse_size("2kb"), Ok(2048));
+        assert_eq!(parse_size(" 1 GiB "), Ok(1 << 30));
+        assert!(parse_size("big").is_err());
+        assert!(parse_size("10X").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30d"), Ok(Duration::from_secs(30 * 86_400)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5_400)));
+        assert!(parse_duration("soon").is_err());
+    }
+
+    #[test]
+    fn test_apply_size_bounds() {
+        let dir = tempdir().unwrap();
+        let small = dir.path().join("small.mp4");
+        let large = dir.path().join("large.mp4");
+        fs::write(&small, vec![0u8; 100]).unwrap();
+        fs::write(&large, vec![0u8; 5000]).unwrap();
+        let videos = vec![small.clone(), large.clone(), dir.path().join("gone.mp4")];
+        let mut durations = DurationCache::new();
+
+        let filter = CandidateFilter {
+            min_size: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(apply(&filter, &videos, &mut durations), vec![large.clone()]);
+
+        let filter = CandidateFilter {
+            max_size: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(apply(&filter, &videos, &mut durations), vec![small.clone()]);
+
+        // A file that grew in place is judged by its current size.
+        fs::write(&small, vec![0u8; 5000]).unwrap();
+        let filter = CandidateFilter {
+            min_size: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(apply(&filter, &videos, &mut durations), [small, large]);
+
+        // Without bounds the list is passed through untouched, even for missing files.
+        assert_eq!(
+            apply(&CandidateFilter::default(), &videos, &mut durations),
+            videos
+        );
+    }
+
+    #[test]
+    fn test_apply_modified_within() {
+        let dir = tempdir().unwrap();
+        let recent = dir.path().join("recent.mp4");
+        let old = dir.path().join("old.mp4");
+        File::create(&recent).unwrap();
+        File::create(&old)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(90 * 86_400))
+            .unwrap();
+
+        let filter = CandidateFilter {
+            modified_within: Some(Duration::from_secs(30 * 86_400)),
+            ..Default::default()
+        };
+        let videos = vec![old, recent.clone()];
+        assert_eq!(
+            apply(&filter, &videos, &mut DurationCache::new()),
+            vec![recent]
+        );
+    }
+
+    #[test]
+    fn test_apply_duration_uses_cache() {
+        let short = PathBuf::from("/videos/short.mp4");
+        let long = PathBuf::from("/videos/long.mp4");
+        let unknown = PathBuf::from("/videos/unknown.mp4");
+        let mut durations = DurationCache::from([
+            (short.clone(), Some(600.0)),
+            (long.clone(), Some(7_200.0)),
+            (unknown.clone(), None),
+        ]);
+
+        let filter = CandidateFilter {
+            max_duration: Some(Duration::from_secs(1_800)),
+            ..Default::default()
+        };
+        let videos = vec![short.clone(), long.clone(), unknown.clone()];
+        assert_eq!(apply(&filter, &videos, &mut durations), vec![short]);
+
+        let filter = CandidateFilter {
+            min_duration: Some(Duration::from_secs(3_600)),
+            ..Default::default()
+        };
+        assert_eq!(apply(&filter, &videos, &mut durations), vec![long]);
+    }
+
+    #[test]
+    fn test_cache_reads_files_again_only_when_needed() {
+        let dir = tempdir().unwrap();
+        let video = dir.path().join("video.mp4");
+        fs::write(&video, vec![0u8; 5000]).unwrap();
+        let filter = CandidateFilter {
+            min_size: Some(1000),
+            modified_within: Some(Duration::from_secs(86_400)),
+            ..Default::default()
+        };
+        let videos = vec![video.clone()];
+        let mut durations = DurationCache::new();
+        let now = SystemTime::now();
+
+        let mut cache = CandidateCache::default();
+        assert_eq!(cache.get(&filter, &videos, now, &mut durations), videos);
+        fs::write(&video, vec![0u8; 100]).unwrap();
+        // Neither the videos nor the filter changed, so the file is not looked at again.
+        assert_eq!(cache.get(&filter, &videos, now, &mut durations), videos);
+        // The modification time bound is checked again as time passes.
+        let later = now + Duration::from_secs(2 * 86_400);
+        assert!(cache
+            .get(&filter, &videos, later, &mut durations)
+            .is_empty());
+
+        let stricter = CandidateFilter {
+            min_size: Some(2000),
+            ..filter
+        };
+        assert!(cache
+            .get(&stricter, &videos, now, &mut durations)
+            .is_empty());
+        assert_eq!(
+            CandidateCache::default().get(
+                &CandidateFilter::default(),
+                &videos,
+                later,
+                &mut durations
+            ),
+            videos
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(CandidateFilter::default().to_string(), "none");
+        let filter = CandidateFilter {
+            min_size: Some(700 << 20),
+            max_duration: Some(Duration::from_secs(1_800)),
+            ..Default::default()
+        };
+        assert_eq!(filter.to_string(), "size ≥ 700.0 MiB, duration ≤ 30m");
+    }
+}
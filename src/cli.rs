@@ -1,6 +1,10 @@
 // src/cli.rs
 
-use clap::Parser;
+use crate::candidate_filter::{parse_duration, parse_size};
+use crate::preferences::parse_favorite_weight;
+use crate::selection_strategy::StrategyKind;
+use clap::{Parser, Subcommand};
+use std::{path::PathBuf, time::Duration};
 
 #[derive(Parser, Debug)]
 #[clap(
@@ -10,13 +14,162 @@ use clap::Parser;
     long_about = None
 )]
 pub struct Cli {
-    #[clap(short, long)]
-    pub folder: Option<String>,
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// Folder to pick videos from. Repeat to combine several folders into one library.
+    #[clap(short, long, value_name = "PATH")]
+    pub folder: Vec<String>,
 
     #[clap(long, action = clap::ArgAction::SetTrue)]
     pub non_recursive: bool,
 
+    /// Only pick files matching this glob (repeatable). Patterns without a `/` match file names.
+    #[clap(long = "include", value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Skip files and directories matching this glob (repeatable), e.g. `sample.*` or `Extras`.
+    #[clap(long = "exclude", value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Comma-separated video extensions to recognize, replacing the defaults (e.g. `mp4,mkv,ts`).
+    #[clap(long, value_delimiter = ',', value_name = "EXT")]
+    pub extensions: Option<Vec<String>>,
+
+    /// Also detect videos by container signature (MP4, Matroska, AVI, MPEG-TS/PS), regardless of extension.
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    pub detect_by_content: bool,
+
+    /// Descend into symlinked directories (each directory is still visited only once).
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    pub follow_symlinks: bool,
+
+    /// Include hidden (dot-prefixed) files and directories.
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    pub include_hidden: bool,
+
+    /// Do not cross into directories mounted from other filesystems.
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    pub same_filesystem: bool,
+
+    /// Maximum directory depth to scan (1 = only the folder itself).
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_depth: Option<u32>,
+
+    /// Number of threads reading directories in parallel (default 8). Raise it for slow network shares.
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub scan_threads: Option<u32>,
+
+    /// Abort the scan on the first unreadable file or subdirectory instead of skipping it.
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    pub strict_scan: bool,
+
+    /// Only pick videos of at least this size (e.g. `700M`, `1.5G`).
+    #[clap(long, value_name = "SIZE", value_parser = parse_size)]
+    pub min_size: Option<u64>,
+
+    /// Only pick videos of at most this size (e.g. `700M`, `1.5G`).
+    #[clap(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_size: Option<u64>,
+
+    /// Only pick videos modified within this long (e.g. `30d`, `12h`).
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub modified_within: Option<Duration>,
+
+    /// Only pick videos at least this long (e.g. `20m`). Probes candidates with ffprobe.
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub min_duration: Option<Duration>,
+
+    /// Only pick videos at most this long (e.g. `1h30m`). Probes candidates with ffprobe.
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub max_duration: Option<Duration>,
+
+    /// How the next video is chosen from the candidates.
+    #[clap(long, value_enum, value_name = "STRATEGY", default_value_t = StrategyKind::Weighted)]
+    pub strategy: StrategyKind,
+
+    /// With `--strategy decayed`: how long until a pick counts half as much (default `30d`).
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub half_life: Option<Duration>,
+
+    /// With `--strategy folder-balanced`: how many levels below each folder to balance (default 1).
+    #[clap(long, value_name = "N")]
+    pub folder_depth: Option<usize>,
+
+    /// How much more likely favorites are to be picked (default 3; must be above 0).
+    #[clap(long, value_name = "FACTOR", value_parser = parse_favorite_weight)]
+    pub favorite_weight: Option<f64>,
+
+    /// Never pick a video that was among the last N picks (relaxed if nothing else is left).
+    #[clap(long, value_name = "N")]
+    pub no_repeat_picks: Option<usize>,
+
+    /// Never pick a video picked within this long (e.g. `3d`; relaxed if nothing else is left).
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub no_repeat_within: Option<Duration>,
+
+    /// Draw a playlist of N distinct videos instead of a single pick.
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(usize))]
+    pub playlist: Option<usize>,
+
+    /// Draw a playlist of distinct videos adding up to about this long (e.g. `2h`).
+    #[clap(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub playlist_duration: Option<Duration>,
+
+    /// Seed for the random number generator, to replay a session's picks (printed with each pick).
+    ///
+    /// A replay only gives the same picks for the same files and history. `--strategy decayed`
+    /// and `--no-repeat-within` also depend on the current time, so the seed alone does not
+    /// reproduce their picks at a later time.
+    #[clap(long, value_name = "SEED")]
+    pub seed: Option<u64>,
+
+    /// Treat identical files (same size and content) as one video for selection and history.
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    pub detect_duplicates: bool,
+
+    /// Compare whole files when detecting duplicates, not just their first and last 64 KiB.
+    #[clap(long, action = clap::ArgAction::SetTrue)]
+    pub full_hash: bool,
+
     /// Disable the remote streaming server entirely.
     #[clap(long, name = "no-streaming", action = clap::ArgAction::SetTrue)]
     pub no_streaming: bool,
+
+    /// Ignore the on-disk library index and walk the whole folder on every scan.
+    #[clap(long, name = "no-index", action = clap::ArgAction::SetTrue)]
+    pub no_index: bool,
+
+    /// Do not watch the scanned folder for added, removed or renamed videos.
+    #[clap(long, name = "no-watch", action = clap::ArgAction::SetTrue)]
+    pub no_watch: bool,
+}
+
+/// Commands that run instead of the interactive picker.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// List groups of identical video files in the library, so they can be cleaned up.
+    Duplicates,
+    /// Manage the pick history.
+    History {
+        #[clap(subcommand)]
+        command: HistoryCommand,
+    },
+}
+
+/// Subcommands of `history`.
+#[derive(Subcommand, Debug)]
+pub enum HistoryCommand {
+    /// Copy a JSON history into the SQLite history database. Picks already there are skipped.
+    Import {
+        /// The JSON history file to import (default: the picker's own `history.json`).
+        #[clap(value_name = "PATH")]
+        file: Option<PathBuf>,
+    },
+    /// Apply the `history_retention` policy from the config file to the history now.
+    Prune {
+        /// Only show what would be removed.
+        #[clap(long)]
+        dry_run: bool,
+    },
 }
\ No newline at end of file
@@ -1,11 +1,47 @@
 // src/config.rs
 
-/// A list of recognized video file extensions (all lowercase).
+/// The default list of recognized video file extensions (all lowercase).
+/// Can be replaced via `video_extensions` in the config file or `--extensions`.
 pub const VIDEO_EXTENSIONS: &[&str] = &[
     "mp4", "mkv", "avi", "mov", "webm", "flv", "wmv", "mpg", "mpeg", "m4v",
 ];
 /// The filename for storing the history of picked videos.
 pub const HISTORY_FILE_NAME: &str = "history.json";
+/// The filename of the SQLite database used for the history with `"history_backend": "sqlite"`.
+pub const HISTORY_DATABASE_FILE_NAME: &str = "history.sqlite3";
+/// How many rating records the history log may accumulate before it is compacted in the
+/// background, folding each rating into the pick it belongs to.
+pub const HISTORY_COMPACTION_THRESHOLD: usize = 100;
+/// How far, in percent of its limits, the history may grow past the retention policy
+/// before it is pruned again. Pruning the JSON log rewrites it, so it is not done on
+/// every pick.
+pub const HISTORY_PRUNE_SLACK_PERCENT: u32 = 10;
+/// The filename for the on-disk library index of scanned directories.
+pub const LIBRARY_INDEX_FILE_NAME: &str = "library_index.json";
+/// The filename for the persisted shuffle bags (videos not yet drawn in the current cycle).
+pub const SHUFFLE_BAG_FILE_NAME: &str = "shuffle_bags.json";
+/// The filename for the favorites and blocklist.
+pub const PREFERENCES_FILE_NAME: &str = "preferences.json";
+/// The filename of the temporary M3U file handed to the system player to play a playlist.
+pub const PLAYLIST_TEMP_FILE_NAME: &str = "file_picker_playlist.m3u";
+/// The filename of the optional user config file (JSON) in the app data directory.
+pub const SETTINGS_FILE_NAME: &str = "config.json";
+/// The name of the gitignore-style files that exclude paths from scans in their directory tree.
+pub const IGNORE_FILE_NAME: &str = ".pickerignore";
+/// The default number of worker threads reading directories during a scan. Directory
+/// reads are I/O-bound (especially on network shares), so this exceeds the core count on
+/// most machines. Can be changed with `--scan-threads`.
+pub const DEFAULT_SCAN_THREADS: usize = 8;
+/// The default half-life of a pick's penalty for recency-decayed weighting.
+/// Can be changed with `--half-life`.
+pub const DEFAULT_HALF_LIFE: std::time::Duration =
+    std::time::Duration::from_secs(30 * 24 * 60 * 60);
+/// The default number of levels below a library root at which folder-balanced
+/// selection groups videos. Can be changed with `--folder-depth` or in the config file.
+pub const DEFAULT_FOLDER_DEPTH: usize = 1;
+/// The default factor a favorite's selection weight is multiplied by.
+/// Can be changed with `--favorite-weight` or in the config file.
+pub const DEFAULT_FAVORITE_WEIGHT: f64 = 3.0;
 /// The application name, used for creating the application-specific data directory.
 pub const APP_NAME: &str = "random_video_picker";
 
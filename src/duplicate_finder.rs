@@ -0,0 +1,432 @@
+// src/duplicate_finder.rs
+
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+/// Bytes hashed from both the start and the end of a file for the partial hash.
+const PARTIAL_HASH_CHUNK: u64 = 64 * 1024;
+/// Buffer size used when hashing whole files.
+const FULL_HASH_BUFFER: usize = 1024 * 1024;
+
+/// A SHA-256 digest of (part of) a file's contents.
+type ContentHash = [u8; 32];
+
+/// How thoroughly candidate duplicates are compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashMode {
+    /// Same size and same first and last 64 KiB. Fast, and reliable for video files,
+    /// whose headers and trailers differ between encodes.
+    Partial,
+    /// Additionally hashes the entire contents of every candidate.
+    Full,
+}
+
+/// Hashes computed for a file during the session, valid while its size and mtime match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedHashes {
+    size: u64,
+    modified: Option<SystemTime>,
+    partial: Option<ContentHash>,
+    full: Option<ContentHash>,
+}
+
+/// Content hashes computed earlier in the session, keyed by path, so that repeated
+/// duplicate passes only read files that are new or have changed.
+pub type HashCache = HashMap<PathBuf, CachedHashes>;
+
+/// A set of files with identical contents.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateGroup {
+    /// The size of each copy in bytes.
+    pub size: u64,
+    /// The copies, sorted. The first one represents the group in selection and history.
+    pub paths: Vec<PathBuf>,
+}
+
+/// The duplicate groups found in a library, with a lookup from each copy to its group.
+#[derive(Debug, Clone, Default)]
+pub struct DuplicateIndex {
+    groups: Vec<DuplicateGroup>,
+    group_of: HashMap<PathBuf, usize>,
+}
+
+/// The duplicate index of the last pass, reused until the scanned videos change, so the
+/// library is only checked again after a scan or a watcher update.
+#[derive(Debug, Default)]
+pub struct DuplicateCache {
+    /// The mode the index was built with; `None` before the first pass.
+    mode: Option<HashMode>,
+    /// The videos the index was built from.
+    videos: Vec<PathBuf>,
+    /// The duplicate groups among `videos`.
+    index: DuplicateIndex,
+}
+
+/// Hashes the first and last `PARTIAL_HASH_CHUNK` bytes of a file.
+fn partial_hash(path: &Path, size: u64) -> io::Result<ContentHash> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; PARTIAL_HASH_CHUNK.min(size) as usize];
+    file.read_exact(&mut buffer)?;
+    hasher.update(&buffer);
+    if size > PARTIAL_HASH_CHUNK {
+        let tail_start = size
+            .saturating_sub(PARTIAL_HASH_CHUNK)
+            .max(PARTIAL_HASH_CHUNK);
+        let mut tail = vec![0u8; (size - tail_start) as usize];
+        file.seek(SeekFrom::Start(tail_start))?;
+        file.read_exact(&mut tail)?;
+        hasher.update(&tail);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// Hashes the entire contents of a file.
+fn full_hash(path: &Path) -> io::Result<ContentHash> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; FULL_HASH_BUFFER];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// Splits each group further by `key`, dropping files the key cannot be computed for
+/// and groups that end up with a single member.
+fn refine<K, F>(groups: Vec<Vec<PathBuf>>, key: F) -> Vec<Vec<PathBuf>>
+where
+    K: std::hash::Hash + Eq + Send,
+    F: Fn(&Path) -> Option<K> + Sync,
+{
+    groups
+        .into_par_iter()
+        .flat_map_iter(|group| {
+            let mut by_key: HashMap<K, Vec<PathBuf>> = HashMap::new();
+            for path in group {
+                if let Some(k) = key(&path) {
+                    by_key.entry(k).or_default().push(path);
+                }
+            }
+            by_key.into_values().filter(|paths| paths.len() > 1)
+        })
+        .collect()
+}
+
+/// Looks up a hash in the cache, computing it on a miss.
+fn cached_hash(
+    cache: &HashCache,
+    path: &Path,
+    which: fn(&CachedHashes) -> Option<ContentHash>,
+    compute: impl FnOnce() -> io::Result<ContentHash>,
+) -> Option<ContentHash> {
+    if let Some(hash) = cache.get(path).and_then(which) {
+        return Some(hash);
+    }
+    match compute() {
+        Ok(hash) => Some(hash),
+        Err(e) => {
+            log::warn!("Could not hash '{}': {}", path.display(), e);
+            None
+        }
+    }
+}
+
+impl DuplicateIndex {
+    /// Finds groups of identical files among `videos`.
+    /// Files are first grouped by size, and only files sharing a size are hashed, so a
+    /// library without duplicates costs little more than a `stat` per file.
+    /// Empty files and files that cannot be read are never reported as duplicates.
+    ///
+    /// # Arguments
+    ///
+    /// * `videos` - The scanned video paths.
+    /// * `mode` - Whether to confirm partial matches by hashing the whole files.
+    /// * `cache` - Hashes from earlier passes; updated with the hashes computed here.
+    pub fn find(videos: &[PathBuf], mode: HashMode, cache: &mut HashCache) -> Self {
+        let stats: Vec<(PathBuf, u64, Option<SystemTime>)> = videos
+            .par_iter()
+            .filter_map(|path| match fs::metadata(path) {
+                Ok(metadata) => Some((path.clone(), metadata.len(), metadata.modified().ok())),
+                Err(e) => {
+                    log::debug!("Could not read metadata of '{}': {}", path.display(), e);
+                    None
+                }
+            })
+            .collect();
+
+        let mut sizes = HashMap::new();
+        let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+        for (path, size, modified) in stats {
+            // Drop cached hashes of files that changed since they were hashed.
+            if cache
+                .get(&path)
+                .is_some_and(|cached| cached.size != size || cached.modified != modified)
+            {
+                cache.remove(&path);
+            }
+            if size > 0 {
+                by_size.entry(size).or_default().push(path.clone());
+            }
+            sizes.insert(path, (size, modified));
+        }
+        let candidates: Vec<Vec<PathBuf>> = by_size
+            .into_values()
+            .filter(|paths| paths.len() > 1)
+            .collect();
+
+        let known: &HashCache = cache;
+        let partial_hashes: Mutex<Vec<(PathBuf, ContentHash)>> = Mutex::new(Vec::new());
+        let mut groups = refine(candidates, |path| {
+            let size = sizes[path].0;
+            let hash = cached_hash(known, path, |c| c.partial, || partial_hash(path, size))?;
+            partial_hashes
+                .lock()
+                .unwrap()
+                .push((path.to_path_buf(), hash));
+            Some((size, hash))
+        });
+        let full_hashes: Mutex<Vec<(PathBuf, ContentHash)>> = Mutex::new(Vec::new());
+        if mode == HashMode::Full {
+            groups = refine(groups, |path| {
+                let hash = cached_hash(known, path, |c| c.full, || full_hash(path))?;
+                full_hashes.lock().unwrap().push((path.to_path_buf(), hash));
+                Some(hash)
+            });
+        }
+
+        let mut record = |path: PathBuf, update: &dyn Fn(&mut CachedHashes)| {
+            let (size, modified) = sizes[&path];
+            let entry = cache.entry(path).or_insert(CachedHashes {
+                size,
+                modified,
+                partial: None,
+                full: None,
+            });
+            update(entry);
+        };
+        for (path, hash) in partial_hashes.into_inner().unwrap() {
+            record(path, &|entry| entry.partial = Some(hash));
+        }
+        for (path, hash) in full_hashes.into_inner().unwrap() {
+            record(path, &|entry| entry.full = Some(hash));
+        }
+
+        let mut groups: Vec<DuplicateGroup> = groups
+            .into_iter()
+            .map(|mut paths| {
+                paths.sort();
+                DuplicateGroup {
+                    size: sizes[&paths[0]].0,
+                    paths,
+                }
+            })
+            .collect();
+        groups.sort_by(|a, b| a.paths[0].cmp(&b.paths[0]));
+        let group_of = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(i, group)| group.paths.iter().map(move |path| (path.clone(), i)))
+            .collect();
+        Self { groups, group_of }
+    }
+
+    /// The duplicate groups, sorted by their first path.
+    pub fn groups(&self) -> &[DuplicateGroup] {
+        &self.groups
+    }
+
+    /// Returns the path that represents `path`'s group: the group's first copy, or
+    /// `path` itself if it has no duplicates.
+    pub fn canonical<'a>(&'a self, path: &'a Path) -> &'a Path {
+        match self.group_of.get(path) {
+            Some(&i) => &self.groups[i].paths[0],
+            None => path,
+        }
+    }
+
+    /// Returns every copy of `path`: `path` itself, then its other copies, if it has any.
+    pub fn copies<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = &'a Path> {
+        std::iter::once(path).chain(self.other_copies(path).map(PathBuf::as_path))
+    }
+
+    /// Returns the other copies of `path`, if it has any.
+    pub fn other_copies<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = &'a PathBuf> {
+        self.group_of
+            .get(path)
+            .map(|&i| self.groups[i].paths.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter(move |copy| copy.as_path() != path)
+    }
+}
+
+impl DuplicateCache {
+    /// Returns the duplicate groups among `videos`, finding them again only if the videos
+    /// or the mode changed since the last call. See `DuplicateIndex::find`.
+    pub fn get(
+        &mut self,
+        videos: &[PathBuf],
+        mode: HashMode,
+        hashes: &mut HashCache,
+    ) -> &DuplicateIndex {
+        if self.mode != Some(mode) || self.videos != videos {
+            self.index = DuplicateIndex::find(videos, mode, hashes);
+            self.mode = Some(mode);
+            self.videos = videos.to_vec();
+        }
+        &self.index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// Writes `len` bytes where every byte is `fill`, except for `marker` at `offset`.
+    fn write_file(path: &Path, len: usize, fill: u8, marker: Option<(usize, u8)>) {
+        let mut contents = vec![fill; len];
+        if let Some((offset, byte)) = marker {
+            contents[offset] = byte;
+        }
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_find_groups_identical_files() {
+        let dir = tempdir().unwrap();
+        let movie = dir.path().join("movie.mkv");
+        let copy = dir.path().join("Movie (copy).mkv");
+        let other = dir.path().join("other.mkv");
+        let empty_a = dir.path().join("a.mkv");
+        let empty_b = dir.path().join("b.mkv");
+        write_file(&movie, 1000, 1, None);
+        write_file(&copy, 1000, 1, None);
+        write_file(&other, 1000, 2, None); // Same size, different contents.
+        write_file(&empty_a, 0, 0, None);
+        write_file(&empty_b, 0, 0, None);
+        let videos = vec![
+            movie.clone(),
+            copy.clone(),
+            other.clone(),
+            empty_a,
+            empty_b,
+            dir.path().join("missing.mkv"),
+        ];
+
+        let index = DuplicateIndex::find(&videos, HashMode::Partial, &mut HashCache::new());
+        assert_eq!(
+            index.groups(),
+            &[DuplicateGroup {
+                size: 1000,
+                paths: vec![copy.clone(), movie.clone()],
+            }]
+        );
+        assert_eq!(index.canonical(&movie), copy.as_path());
+        assert_eq!(index.canonical(&other), other.as_path());
+        assert_eq!(index.other_copies(&copy).collect::<Vec<_>>(), vec![&movie]);
+        assert_eq!(index.other_copies(&other).count(), 0);
+        assert_eq!(
+            index.copies(&movie).collect::<Vec<_>>(),
+            [movie.as_path(), copy.as_path()]
+        );
+        assert_eq!(index.copies(&other).collect::<Vec<_>>(), [other.as_path()]);
+    }
+
+    #[test]
+    fn test_hashes_are_sha256() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("abc.mkv");
+        fs::write(&file, "abc").unwrap();
+        let expected: ContentHash = [
+            0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae,
+            0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+            0xf2, 0x00, 0x15, 0xad,
+        ];
+        assert_eq!(full_hash(&file).unwrap(), expected);
+        // A file shorter than a chunk is hashed whole by the partial hash too.
+        assert_eq!(partial_hash(&file, 3).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_full_hash_separates_files_differing_in_the_middle() {
+        let dir = tempdir().unwrap();
+        let len = (PARTIAL_HASH_CHUNK * 3) as usize;
+        let a = dir.path().join("a.mkv");
+        let b = dir.path().join("b.mkv");
+        write_file(&a, len, 7, None);
+        write_file(&b, len, 7, Some((len / 2, 8)));
+        let videos = vec![a, b];
+
+        let mut cache = HashCache::new();
+        let partial = DuplicateIndex::find(&videos, HashMode::Partial, &mut cache);
+        assert_eq!(partial.groups().len(), 1);
+        let full = DuplicateIndex::find(&videos, HashMode::Full, &mut cache);
+        assert!(full.groups().is_empty());
+    }
+
+    #[test]
+    fn test_cache_finds_again_only_when_videos_change() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.mkv");
+        let b = dir.path().join("b.mkv");
+        let c = dir.path().join("c.mkv");
+        write_file(&a, 100, 1, None);
+        write_file(&b, 100, 1, None);
+        write_file(&c, 100, 1, None);
+        let mut videos = vec![a.clone(), b.clone()];
+
+        let mut cache = DuplicateCache::default();
+        let mut hashes = HashCache::new();
+        assert_eq!(cache.get(&videos, HashMode::Partial, &mut hashes).groups().len(), 1);
+        // With the same videos the files are not looked at again.
+        fs::remove_file(&b).unwrap();
+        assert_eq!(cache.get(&videos, HashMode::Partial, &mut hashes).groups().len(), 1);
+
+        videos[1] = c.clone();
+        let index = cache.get(&videos, HashMode::Partial, &mut hashes);
+        assert_eq!(index.groups()[0].paths, [a, c]);
+    }
+
+    #[test]
+    fn test_changed_file_is_rehashed() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.mkv");
+        let b = dir.path().join("b.mkv");
+        write_file(&a, 100, 1, None);
+        write_file(&b, 100, 1, None);
+        let videos = vec![a, b.clone()];
+
+        let mut cache = HashCache::new();
+        assert_eq!(
+            DuplicateIndex::find(&videos, HashMode::Partial, &mut cache)
+                .groups()
+                .len(),
+            1
+        );
+        write_file(&b, 100, 2, None);
+        // Keep the size; force a different mtime in case the rewrite landed in the same tick.
+        File::options()
+            .write(true)
+            .open(&b)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        assert!(DuplicateIndex::find(&videos, HashMode::Partial, &mut cache)
+            .groups()
+            .is_empty());
+    }
+}
@@ -1,12 +1,22 @@
 // src/file_utils.rs
 
-use crate::config::{APP_NAME, HISTORY_FILE_NAME, VIDEO_EXTENSIONS};
+use crate::config::{
+    APP_NAME, DEFAULT_SCAN_THREADS, HISTORY_DATABASE_FILE_NAME, HISTORY_FILE_NAME, IGNORE_FILE_NAME,
+    LIBRARY_INDEX_FILE_NAME, PREFERENCES_FILE_NAME, SETTINGS_FILE_NAME, SHUFFLE_BAG_FILE_NAME,
+    VIDEO_EXTENSIONS,
+};
+use crate::scan_filter::{IgnoreRules, ScanFilter};
+use crate::video_sniffer::sniff_video_container;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::{
-    fs,
-    io::{Error as IoError, ErrorKind as IoErrorKind},
+    collections::HashSet,
+    fs::{self, File},
+    io::{BufWriter, Error as IoError, ErrorKind as IoErrorKind},
     path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::SystemTime,
 };
-use walkdir::WalkDir;
 
 /// Returns the full path to the application's data directory.
 /// This function creates the directory if it does not already exist.
@@ -40,23 +50,501 @@ pub fn get_history_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
     Ok(get_app_data_dir()?.join(HISTORY_FILE_NAME))
 }
 
-/// Scans the specified folder for files with recognized video extensions.
-/// The scan can be performed recursively.
+/// Returns the full path to the SQLite history database, located within the app data directory.
 ///
-/// # Arguments
+/// # Errors
 ///
-/// * `folder_path` - The path to the directory to be scanned.
-/// * `recursive` - If true, subdirectories are scanned; otherwise, only the top-level directory is scanned.
+/// Returns an error if the application data directory cannot be determined.
+pub fn get_history_database_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(get_app_data_dir()?.join(HISTORY_DATABASE_FILE_NAME))
+}
+
+/// Returns the full path to the library index file, located within the app data directory.
 ///
 /// # Errors
 ///
-/// Returns an error if:
-/// * `folder_path` is not a valid directory.
-/// * An issue occurs while accessing files or directories during the scan.
-pub fn find_video_files(
+/// Returns an error if the application data directory cannot be determined.
+pub fn get_library_index_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(get_app_data_dir()?.join(LIBRARY_INDEX_FILE_NAME))
+}
+
+/// Returns the full path to the shuffle bag file, located within the app data directory.
+///
+/// # Errors
+///
+/// Returns an error if the application data directory cannot be determined.
+pub fn get_shuffle_bag_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(get_app_data_dir()?.join(SHUFFLE_BAG_FILE_NAME))
+}
+
+/// Returns the full path to the favorites and blocklist file, located within the app
+/// data directory.
+///
+/// # Errors
+///
+/// Returns an error if the application data directory cannot be determined.
+pub fn get_preferences_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(get_app_data_dir()?.join(PREFERENCES_FILE_NAME))
+}
+
+/// Returns the full path to the user config file, located within the app data directory.
+///
+/// # Errors
+///
+/// Returns an error if the application data directory cannot be determined.
+pub fn get_settings_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(get_app_data_dir()?.join(SETTINGS_FILE_NAME))
+}
+
+/// Replaces the file at `path` with the output of `write` without ever leaving a partial
+/// file behind: the contents go to a temporary file in the same directory, which is
+/// flushed to disk and then renamed over `path`. If anything fails, `path` keeps its
+/// previous contents and the temporary file is removed.
+///
+/// # Errors
+///
+/// Returns an error if `write` fails, or if the temporary file cannot be created,
+/// synced or renamed.
+pub fn atomic_write(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<(), IoError>,
+) -> Result<(), IoError> {
+    let file_name = path.file_name().ok_or_else(|| {
+        IoError::new(
+            IoErrorKind::InvalidInput,
+            format!("'{}' is not a file path", path.display()),
+        )
+    })?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = dir.join(temp_name);
+
+    let result = (|| {
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        write(&mut writer)?;
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+
+    // Persist the rename itself. Directories cannot be opened as files on Windows.
+    #[cfg(unix)]
+    if let Err(e) = File::open(dir).and_then(|dir| dir.sync_all()) {
+        log::debug!("Could not sync directory '{}': {}", dir.display(), e);
+    }
+    Ok(())
+}
+
+/// A file seen during a scan, with the size and modification time recorded at that point.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FileRecord {
+    /// The full path to the file.
+    pub path: PathBuf,
+    /// The file size in bytes.
+    pub size: u64,
+    /// The file's last modification time, if the platform reports one.
+    pub modified: Option<SystemTime>,
+}
+
+/// The direct contents of a single directory, as seen by the scanner.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DirectoryListing {
+    /// The directory's own modification time when it was read.
+    pub modified: Option<SystemTime>,
+    /// Subdirectories directly inside this directory (sorted).
+    pub subdirectories: Vec<PathBuf>,
+    /// Symlinks to directories directly inside this directory (sorted).
+    /// Only descended into when `ScanOptions::follow_symlinks` is set.
+    #[serde(default)]
+    pub symlinked_subdirectories: Vec<PathBuf>,
+    /// Files directly inside this directory (sorted by path). All files are kept so that
+    /// cached listings stay valid when the set of video extensions changes.
+    pub files: Vec<FileRecord>,
+    /// Whether the directory contains a `.pickerignore` file.
+    #[serde(default)]
+    pub has_ignore_file: bool,
+    /// Entries that could not be read. Listings with skipped entries are never cached.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedEntry>,
+    /// The directory's identity at the time it was listed. Not persisted, since device
+    /// numbers can change between boots; cached listings get a fresh identity on reuse.
+    #[serde(skip)]
+    pub identity: Option<DirIdentity>,
+}
+
+/// Identifies a directory independently of the path used to reach it, so that symlink
+/// loops and bind mounts looping back into the tree are only visited once.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DirIdentity {
+    /// Device and inode numbers (Unix).
+    Inode { device: u64, inode: u64 },
+    /// Canonical path, used where inode numbers are not available.
+    #[cfg_attr(unix, allow(dead_code))]
+    Path(PathBuf),
+}
+
+impl DirIdentity {
+    /// Determines the identity of `dir` from its (already fetched) metadata.
+    pub fn of(dir: &Path, metadata: &fs::Metadata) -> Option<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let _ = dir;
+            Some(Self::Inode {
+                device: metadata.dev(),
+                inode: metadata.ino(),
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = metadata;
+            fs::canonicalize(dir).ok().map(Self::Path)
+        }
+    }
+
+    /// The device the directory lives on, if known.
+    pub fn device(&self) -> Option<u64> {
+        match self {
+            Self::Inode { device, .. } => Some(*device),
+            Self::Path(_) => None,
+        }
+    }
+}
+
+/// A file or directory the scanner could not read, and why.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SkippedEntry {
+    /// The path that was skipped.
+    pub path: PathBuf,
+    /// A description of the error that caused it to be skipped.
+    pub reason: String,
+}
+
+/// Live counters updated while a scan runs, so callers can show progress from another thread.
+#[derive(Debug, Default)]
+pub struct ScanProgress {
+    directories: AtomicUsize,
+    videos: AtomicUsize,
+}
+
+impl ScanProgress {
+    /// Number of directories read (or taken from the library index) so far.
+    pub fn directories_visited(&self) -> usize {
+        self.directories.load(Ordering::Relaxed)
+    }
+
+    /// Number of videos found so far.
+    pub fn videos_found(&self) -> usize {
+        self.videos.load(Ordering::Relaxed)
+    }
+}
+
+/// The outcome of a scan: the videos found, plus anything that had to be skipped.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanReport {
+    /// Video files found, sorted.
+    pub videos: Vec<PathBuf>,
+    /// Unreadable entries. Always empty unless `ScanOptions::skip_unreadable` is set.
+    pub skipped: Vec<SkippedEntry>,
+}
+
+impl ScanReport {
+    /// Combines the reports of several library roots into one. Videos found under more
+    /// than one root (e.g. when one root is nested inside another) are listed once.
+    pub fn merge(reports: impl IntoIterator<Item = ScanReport>) -> ScanReport {
+        let mut merged = ScanReport::default();
+        for report in reports {
+            merged.videos.extend(report.videos);
+            merged.skipped.extend(report.skipped);
+        }
+        merged.videos.sort();
+        merged.videos.dedup();
+        merged.skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        merged.skipped.dedup_by(|a, b| a.path == b.path);
+        merged
+    }
+}
+
+/// Removes repeated library roots, comparing canonical paths where they can be resolved
+/// so that `~/Videos` and `/home/me/Videos/` count as the same root. Keeps the first
+/// occurrence of each, in the order given.
+pub fn dedup_roots(roots: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    roots
+        .into_iter()
+        .filter(|root| seen.insert(fs::canonicalize(root).unwrap_or_else(|_| root.clone())))
+        .collect()
+}
+
+/// Returns the library root that `path` was found under. With nested roots the innermost
+/// one is returned.
+pub fn root_of<'a>(path: &Path, roots: &'a [PathBuf]) -> Option<&'a Path> {
+    roots
+        .iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count())
+        .map(PathBuf::as_path)
+}
+
+/// Settings that control which directories a scan visits and which files it reports.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// If true, subdirectories are scanned; otherwise only the top-level directory is scanned.
+    pub recursive: bool,
+    /// Include/exclude patterns. `.pickerignore` files are respected regardless.
+    pub filter: ScanFilter,
+    /// Lowercase file extensions (without the dot) recognized as videos.
+    pub extensions: Vec<String>,
+    /// Also detect videos by container signature. This reads the start of every file
+    /// that does not have a recognized extension, so it is off by default.
+    pub detect_by_content: bool,
+    /// Skip unreadable files and subdirectories (recording them in the `ScanReport`)
+    /// instead of aborting the scan. The scanned folder itself must always be readable.
+    pub skip_unreadable: bool,
+    /// Descend into symlinked directories. Each directory is visited at most once, so
+    /// links (or bind mounts) pointing back up the tree cannot cause endless loops.
+    pub follow_symlinks: bool,
+    /// Include files and directories whose names start with a dot.
+    pub include_hidden: bool,
+    /// Do not descend into directories on a different filesystem than the scanned folder.
+    pub same_filesystem: bool,
+    /// Maximum depth below the scanned folder (1 = only its direct contents).
+    /// `None` means unlimited; ignored when `recursive` is false.
+    pub max_depth: Option<usize>,
+    /// Number of worker threads reading directories (and sniffing files) in parallel.
+    pub threads: usize,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            recursive: false,
+            filter: ScanFilter::default(),
+            extensions: VIDEO_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+            detect_by_content: false,
+            skip_unreadable: false,
+            follow_symlinks: false,
+            include_hidden: false,
+            same_filesystem: false,
+            max_depth: None,
+            threads: DEFAULT_SCAN_THREADS,
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Normalizes a user-supplied extension list: trims whitespace and leading dots,
+    /// lowercases, and drops empty entries and duplicates.
+    pub fn normalize_extensions(extensions: &[String]) -> Vec<String> {
+        let mut normalized: Vec<String> = extensions
+            .iter()
+            .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+            .filter(|ext| !ext.is_empty())
+            .collect();
+        normalized.sort();
+        normalized.dedup();
+        normalized
+    }
+
+    /// The effective maximum depth, taking `recursive` into account.
+    pub fn effective_max_depth(&self) -> Option<usize> {
+        if self.recursive {
+            self.max_depth
+        } else {
+            Some(1)
+        }
+    }
+
+    /// Returns true if a path (relative to the scanned folder) is within the depth limit
+    /// and, unless hidden entries are included, has no dot-prefixed components.
+    pub fn allows_location(&self, relative: &Path) -> bool {
+        let depth = relative.components().count();
+        self.effective_max_depth().is_none_or(|max| depth <= max)
+            && (self.include_hidden
+                || !relative.components().any(|c| is_hidden_name(c.as_os_str())))
+    }
+
+    /// Returns true if the path has one of the recognized video extensions (case-insensitive).
+    pub fn has_video_extension(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|ext| self.extensions.contains(&ext.to_lowercase()))
+    }
+
+    /// Returns true if the file should be treated as a video: either by extension or,
+    /// when content detection is enabled, by its container signature.
+    pub fn is_video_file(&self, path: &Path) -> bool {
+        if self.has_video_extension(path) {
+            return true;
+        }
+        if !self.detect_by_content {
+            return false;
+        }
+        match sniff_video_container(path) {
+            Ok(container) => container.is_some(),
+            Err(e) => {
+                log::debug!("Could not sniff '{}': {}", path.display(), e);
+                false
+            }
+        }
+    }
+}
+
+/// Returns true for dot-prefixed names, which are hidden by convention.
+fn is_hidden_name(name: &std::ffi::OsStr) -> bool {
+    name.to_string_lossy().starts_with('.')
+}
+
+/// Returns true if the last component of `path` is a hidden name.
+fn is_hidden_file(path: &Path) -> bool {
+    path.file_name().is_some_and(is_hidden_name)
+}
+
+/// Reads the direct contents of `dir`: subdirectories and regular files.
+///
+/// Symlinks to files are followed so linked videos are included; symlinked
+/// directories are listed separately.
+/// Entries that cannot be read, including broken symlinks, are recorded in
+/// `DirectoryListing::skipped`.
+///
+/// # Errors
+///
+/// Returns an error if the directory itself cannot be read.
+pub fn read_directory_listing(dir: &Path) -> Result<DirectoryListing, IoError> {
+    let with_context = |e: IoError| {
+        IoError::new(
+            e.kind(),
+            format!("Failed to read directory '{}': {}", dir.display(), e),
+        )
+    };
+
+    let dir_metadata = fs::metadata(dir).map_err(with_context)?;
+    let mut listing = DirectoryListing {
+        modified: dir_metadata.modified().ok(),
+        identity: DirIdentity::of(dir, &dir_metadata),
+        ..Default::default()
+    };
+
+    for entry_result in fs::read_dir(dir).map_err(with_context)? {
+        let entry = match entry_result {
+            Ok(entry) => entry,
+            Err(e) => {
+                listing.skipped.push(SkippedEntry {
+                    path: dir.to_path_buf(),
+                    reason: format!("Failed to read directory entry: {}", e),
+                });
+                continue;
+            }
+        };
+        let path = entry.path();
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(e) => {
+                listing.skipped.push(SkippedEntry {
+                    path,
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+
+        if file_type.is_dir() {
+            listing.subdirectories.push(path);
+            continue;
+        }
+        if entry.file_name() == IGNORE_FILE_NAME {
+            listing.has_ignore_file = true;
+            continue;
+        }
+
+        let metadata = if file_type.is_symlink() {
+            match fs::metadata(&path) {
+                Ok(metadata) if metadata.is_file() => metadata,
+                Ok(metadata) if metadata.is_dir() => {
+                    listing.symlinked_subdirectories.push(path);
+                    continue;
+                }
+                Ok(_) => continue,
+                Err(e) => {
+                    listing.skipped.push(SkippedEntry {
+                        path,
+                        reason: format!("broken symlink ({})", e),
+                    });
+                    continue;
+                }
+            }
+        } else if file_type.is_file() {
+            match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    listing.skipped.push(SkippedEntry {
+                        path,
+                        reason: e.to_string(),
+                    });
+                    continue;
+                }
+            }
+        } else {
+            continue;
+        };
+
+        listing.files.push(FileRecord {
+            path,
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        });
+    }
+
+    listing.subdirectories.sort();
+    listing.symlinked_subdirectories.sort();
+    listing.files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(listing)
+}
+
+/// What a scan keeps from one directory before it is merged into the report.
+struct DirectoryScan {
+    identity: Option<DirIdentity>,
+    skipped: Vec<SkippedEntry>,
+    /// Files in the directory that passed the filters and video detection.
+    videos: Vec<PathBuf>,
+    /// Subdirectories to descend into (empty at the depth limit).
+    subdirs: Vec<PathBuf>,
+    /// Ignore rules in effect for the directory, inherited by its subdirectories.
+    rules: IgnoreRules,
+}
+
+/// Walks the tree under `folder_path`, obtaining each directory's contents from `list_directory`.
+/// This lets callers substitute cached listings for directories that have not changed.
+/// Excluded directories and those ignored by a `.pickerignore` file are not descended into,
+/// and directories already visited through another path (symlink or mount loops) are skipped.
+///
+/// The tree is walked one depth level at a time, with the directories of each level read on
+/// `options.threads` worker threads, so `list_directory` may be called concurrently.
+/// The result does not depend on the number of threads.
+///
+/// # Errors
+///
+/// Returns an error if `folder_path` is not a directory or cannot be listed. Errors for
+/// anything below it are collected in the report when `options.skip_unreadable` is set,
+/// and returned otherwise.
+pub fn collect_video_files<F>(
     folder_path: &Path,
-    recursive: bool,
-) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    options: &ScanOptions,
+    progress: Option<&ScanProgress>,
+    list_directory: F,
+) -> Result<ScanReport, Box<dyn std::error::Error>>
+where
+    F: Fn(&Path) -> Result<DirectoryListing, IoError> + Sync,
+{
     if !folder_path.is_dir() {
         return Err(Box::new(IoError::new(
             IoErrorKind::InvalidInput,
@@ -64,37 +552,214 @@ pub fn find_video_files(
         )));
     }
 
-    let mut video_files = Vec::new();
+    let relative = |path: &Path| path.strip_prefix(folder_path).unwrap_or(path).to_path_buf();
+    let max_depth = options.effective_max_depth();
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.threads.max(1))
+        .build()?;
+    let mut report = ScanReport::default();
+    let mut visited: HashSet<DirIdentity> = HashSet::new();
+    let mut root_device: Option<u64> = None;
+    // Directories at the current depth, each with the ignore rules it inherits.
+    let mut level = vec![(folder_path.to_path_buf(), IgnoreRules::default())];
+    let mut depth = 0usize;
 
-    let walker = WalkDir::new(folder_path).min_depth(1); // Start scanning from depth 1 (contents of the folder)
-    let walker = if recursive {
-        walker // No max_depth results in a fully recursive scan.
-    } else {
-        walker.max_depth(1) // Limit scan to the top-level directory contents. Note: Original had max_depth(5) - adjusted to 1 for true non-recursive.
-    };
-
-    for entry_result in walker {
-        let entry = entry_result?; // Propagate errors encountered during directory walking.
-        let path = entry.path();
+    while !level.is_empty() {
+        let can_descend = max_depth.is_none_or(|max| depth + 1 < max);
+        // Reading and filtering a directory only depends on its parent's ignore rules, so
+        // the whole level is handled in parallel. The results are then merged in order,
+        // which keeps loop detection (and therefore the output) deterministic.
+        let scans: Vec<Result<DirectoryScan, IoError>> = pool.install(|| {
+            level
+                .par_iter()
+                .map(|(dir, inherited_rules)| {
+                    let listing = list_directory(dir)?;
+                    if let Some(progress) = progress {
+                        progress.directories.fetch_add(1, Ordering::Relaxed);
+                    }
+                    let rules = if listing.has_ignore_file {
+                        inherited_rules.with_ignore_file(dir)
+                    } else {
+                        inherited_rules.clone()
+                    };
+                    let videos: Vec<PathBuf> = listing
+                        .files
+                        .iter()
+                        .map(|record| &record.path)
+                        .filter(|path| {
+                            (options.include_hidden || !is_hidden_file(path))
+                                && options.filter.allows_file(&relative(path))
+                                && !rules.is_ignored(path, false)
+                                && options.is_video_file(path)
+                        })
+                        .cloned()
+                        .collect();
+                    let subdirs: Vec<PathBuf> = if can_descend {
+                        let symlinked = if options.follow_symlinks {
+                            &listing.symlinked_subdirectories[..]
+                        } else {
+                            &[]
+                        };
+                        listing
+                            .subdirectories
+                            .iter()
+                            .chain(symlinked)
+                            .filter(|subdir| {
+                                (options.include_hidden || !is_hidden_file(subdir))
+                                    && options.filter.allows_dir(&relative(subdir))
+                                    && !rules.is_ignored(subdir, true)
+                            })
+                            .cloned()
+                            .collect()
+                    } else {
+                        Vec::new()
+                    };
+                    Ok(DirectoryScan {
+                        identity: listing.identity,
+                        skipped: listing.skipped,
+                        videos,
+                        subdirs,
+                        rules,
+                    })
+                })
+                .collect()
+        });
 
-        if path.is_file() {
-            if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                if VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()) {
-                    video_files.push(path.to_path_buf());
+        let mut next_level = Vec::new();
+        for ((dir, _), scan) in level.into_iter().zip(scans) {
+            let DirectoryScan {
+                identity,
+                mut skipped,
+                videos,
+                subdirs,
+                rules,
+            } = match scan {
+                Ok(scan) => scan,
+                // The root must be readable; anything below it may be skipped.
+                Err(e) if options.skip_unreadable && dir != folder_path => {
+                    log::warn!("Skipping '{}': {}", dir.display(), e);
+                    report.skipped.push(SkippedEntry {
+                        path: dir,
+                        reason: e.to_string(),
+                    });
+                    continue;
+                }
+                Err(e) => return Err(Box::new(e)), // Propagate errors encountered during directory walking.
+            };
+            if let Some(identity) = identity {
+                if depth == 0 {
+                    root_device = identity.device();
+                } else if options.same_filesystem && identity.device() != root_device {
+                    log::debug!(
+                        "Not crossing into another filesystem at '{}'.",
+                        dir.display()
+                    );
+                    continue;
+                }
+                if !visited.insert(identity) {
+                    log::debug!(
+                        "Skipping '{}': directory already visited (symlink or mount loop).",
+                        dir.display()
+                    );
+                    continue;
+                }
+            }
+            if let Some(first) = skipped.first() {
+                if !options.skip_unreadable {
+                    return Err(Box::new(IoError::other(format!(
+                        "Failed to read '{}': {}",
+                        first.path.display(),
+                        first.reason
+                    ))));
                 }
+                for entry in &skipped {
+                    log::warn!("Skipping '{}': {}", entry.path.display(), entry.reason);
+                }
+                report.skipped.append(&mut skipped);
+            }
+
+            if let Some(progress) = progress {
+                progress.videos.fetch_add(videos.len(), Ordering::Relaxed);
             }
+            report.videos.extend(videos);
+            next_level.extend(subdirs.into_iter().map(|subdir| (subdir, rules.clone())));
         }
+        level = next_level;
+        depth += 1;
     }
+
     // Sort for deterministic output in tests and UI
-    video_files.sort();
-    Ok(video_files)
+    report.videos.sort();
+    report.skipped.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(report)
+}
+
+/// Scans the specified folder for files with recognized video extensions
+/// (or, optionally, a recognized container signature).
+/// The scan can be performed recursively and filtered with include/exclude patterns.
+///
+/// # Arguments
+///
+/// * `folder_path` - The path to the directory to be scanned.
+/// * `options` - Scan settings: recursion, path filters and video detection.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// * `folder_path` is not a valid directory.
+/// * An issue occurs while accessing files or directories during the scan.
+pub fn find_video_files(
+    folder_path: &Path,
+    options: &ScanOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    Ok(scan_video_files(folder_path, options, None)?.videos)
+}
+
+/// Like `find_video_files`, but also returns the entries that were skipped when
+/// `options.skip_unreadable` is set. If `progress` is given, it is updated as the scan runs.
+///
+/// # Errors
+///
+/// Returns an error if `folder_path` is not a readable directory, or (in strict mode)
+/// if anything below it cannot be read.
+pub fn scan_video_files(
+    folder_path: &Path,
+    options: &ScanOptions,
+    progress: Option<&ScanProgress>,
+) -> Result<ScanReport, Box<dyn std::error::Error>> {
+    collect_video_files(folder_path, options, progress, read_directory_listing)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tempfile::tempdir;
     use std::fs::File;
+    use tempfile::tempdir;
+
+    fn scan_options(recursive: bool) -> ScanOptions {
+        ScanOptions {
+            recursive,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_atomic_write() {
+        use std::io::Write;
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("data.json");
+        atomic_write(&path, |w| w.write_all(b"first")).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+
+        // A failed write leaves the previous contents and no temporary file.
+        let result = atomic_write(&path, |w| {
+            w.write_all(b"partial")?;
+            Err(IoError::other("disk full"))
+        });
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
 
     #[test]
     fn test_find_video_files_recursive() {
@@ -109,7 +774,7 @@ mod tests {
         fs::create_dir(&subdir).unwrap();
         File::create(subdir.join("video2.mkv")).unwrap();
 
-        let files = find_video_files(root, true).unwrap();
+        let files = find_video_files(root, &scan_options(true)).unwrap();
         assert_eq!(files.len(), 2);
 
         let file_names: Vec<String> = files.iter()
@@ -132,18 +797,407 @@ mod tests {
         fs::create_dir(&subdir).unwrap();
         File::create(subdir.join("video2.mkv")).unwrap();
 
-        let files = find_video_files(root, false).unwrap();
+        let files = find_video_files(root, &scan_options(false)).unwrap();
         assert_eq!(files.len(), 1);
         assert_eq!(files[0].file_name().unwrap().to_string_lossy(), "video1.mp4");
     }
 
+    #[test]
+    fn test_scan_video_files_respects_filters_and_ignore_files() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let movie = root.join("Movie");
+        let extras = movie.join("Extras");
+        fs::create_dir_all(&extras).unwrap();
+        fs::create_dir(root.join(".trash")).unwrap();
+        File::create(movie.join("movie.mkv")).unwrap();
+        File::create(movie.join("sample.mkv")).unwrap();
+        File::create(extras.join("featurette.mkv")).unwrap();
+        File::create(root.join(".trash").join("deleted.mp4")).unwrap();
+        File::create(root.join("clip.avi")).unwrap();
+        fs::write(movie.join(IGNORE_FILE_NAME), "Extras/\n").unwrap();
+
+        let options = ScanOptions {
+            recursive: true,
+            filter: ScanFilter::new(&[], &["sample.*".to_string(), ".trash".to_string()]).unwrap(),
+            ..Default::default()
+        };
+        let files = find_video_files(root, &options).unwrap();
+        assert_eq!(
+            files,
+            vec![root.join("Movie").join("movie.mkv"), root.join("clip.avi")]
+        );
+
+        let include_only_avi = ScanOptions {
+            recursive: true,
+            filter: ScanFilter::new(&["*.avi".to_string()], &[]).unwrap(),
+            ..Default::default()
+        };
+        let files = find_video_files(root, &include_only_avi).unwrap();
+        assert_eq!(files, vec![root.join("clip.avi")]);
+    }
+
+    #[test]
+    fn test_find_video_files_custom_extensions_and_content_detection() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        File::create(root.join("video1.mp4")).unwrap();
+        File::create(root.join("broadcast.TS")).unwrap();
+        fs::write(
+            root.join("no_extension"),
+            [0x1A, 0x45, 0xDF, 0xA3, 0x42, 0x86],
+        )
+        .unwrap();
+        fs::write(root.join("notes.txt"), "just text").unwrap();
+
+        let options = ScanOptions {
+            extensions: ScanOptions::normalize_extensions(&[".ts".to_string(), "MP4".to_string()]),
+            ..Default::default()
+        };
+        let files = find_video_files(root, &options).unwrap();
+        assert_eq!(
+            files,
+            vec![root.join("broadcast.TS"), root.join("video1.mp4")]
+        );
+
+        let sniffing = ScanOptions {
+            detect_by_content: true,
+            ..Default::default()
+        };
+        let files = find_video_files(root, &sniffing).unwrap();
+        assert_eq!(
+            files,
+            vec![root.join("no_extension"), root.join("video1.mp4")]
+        );
+    }
+
+    #[test]
+    fn test_collect_video_files_skips_unreadable_subdirectories() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let locked = root.join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::create_dir(root.join("open")).unwrap();
+        File::create(root.join("video1.mp4")).unwrap();
+        File::create(root.join("open").join("video2.mkv")).unwrap();
+
+        // Simulate a permission error without relying on the current user's privileges.
+        let list = |dir: &Path| {
+            if dir == locked {
+                Err(IoError::new(
+                    IoErrorKind::PermissionDenied,
+                    "permission denied",
+                ))
+            } else {
+                read_directory_listing(dir)
+            }
+        };
+
+        let tolerant = ScanOptions {
+            recursive: true,
+            skip_unreadable: true,
+            ..Default::default()
+        };
+        let report = collect_video_files(root, &tolerant, None, list).unwrap();
+        assert_eq!(
+            report.videos,
+            vec![
+                root.join("open").join("video2.mkv"),
+                root.join("video1.mp4")
+            ]
+        );
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].path, locked);
+        assert!(report.skipped[0].reason.contains("permission denied"));
+
+        // Strict mode aborts on the same error.
+        assert!(collect_video_files(root, &scan_options(true), None, list).is_err());
+    }
+
+    #[test]
+    fn test_collect_video_files_fails_if_root_unreadable() {
+        let dir = tempdir().unwrap();
+        let options = ScanOptions {
+            skip_unreadable: true,
+            ..Default::default()
+        };
+        let result = collect_video_files(dir.path(), &options, None, |_| {
+            Err(IoError::new(
+                IoErrorKind::PermissionDenied,
+                "permission denied",
+            ))
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_collect_video_files_reports_skipped_entries() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        File::create(root.join("video1.mp4")).unwrap();
+        let unreadable = root.join("broken.mkv");
+
+        let list = |dir: &Path| {
+            let mut listing = read_directory_listing(dir)?;
+            listing.skipped.push(SkippedEntry {
+                path: unreadable.clone(),
+                reason: "input/output error".to_string(),
+            });
+            Ok(listing)
+        };
+
+        let tolerant = ScanOptions {
+            skip_unreadable: true,
+            ..Default::default()
+        };
+        let report = collect_video_files(root, &tolerant, None, list).unwrap();
+        assert_eq!(report.videos, vec![root.join("video1.mp4")]);
+        assert_eq!(report.skipped[0].path, unreadable);
+
+        assert!(collect_video_files(root, &scan_options(false), None, list).is_err());
+    }
+
+    #[test]
+    fn test_hidden_entries_skipped_unless_included() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join(".cache")).unwrap();
+        File::create(root.join(".cache").join("thumb.mp4")).unwrap();
+        File::create(root.join(".partial.mkv")).unwrap();
+        File::create(root.join("video.mp4")).unwrap();
+
+        let videos = find_video_files(root, &scan_options(true)).unwrap();
+        assert_eq!(videos, vec![root.join("video.mp4")]);
+
+        let options = ScanOptions {
+            include_hidden: true,
+            ..scan_options(true)
+        };
+        let videos = find_video_files(root, &options).unwrap();
+        assert_eq!(
+            videos,
+            vec![
+                root.join(".cache").join("thumb.mp4"),
+                root.join(".partial.mkv"),
+                root.join("video.mp4"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_max_depth_limits_descent() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let deep = root.join("a").join("b");
+        fs::create_dir_all(&deep).unwrap();
+        File::create(root.join("top.mp4")).unwrap();
+        File::create(root.join("a").join("mid.mp4")).unwrap();
+        File::create(deep.join("deep.mp4")).unwrap();
+
+        let with_depth = |max_depth| ScanOptions {
+            max_depth: Some(max_depth),
+            ..scan_options(true)
+        };
+        assert_eq!(
+            find_video_files(root, &with_depth(1)).unwrap(),
+            vec![root.join("top.mp4")]
+        );
+        assert_eq!(
+            find_video_files(root, &with_depth(2)).unwrap(),
+            vec![root.join("a").join("mid.mp4"), root.join("top.mp4")]
+        );
+        assert_eq!(find_video_files(root, &with_depth(3)).unwrap().len(), 3);
+
+        let options = ScanOptions::default();
+        assert!(options.allows_location(Path::new("top.mp4")));
+        assert!(!options.allows_location(Path::new("a/mid.mp4")));
+        assert!(!scan_options(true).allows_location(Path::new(".cache/thumb.mp4")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_broken_symlink_is_reported() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        File::create(root.join("video.mp4")).unwrap();
+        let dangling = root.join("moved.mkv");
+        symlink(root.join("missing.mkv"), &dangling).unwrap();
+
+        let tolerant = ScanOptions {
+            skip_unreadable: true,
+            ..Default::default()
+        };
+        let report = scan_video_files(root, &tolerant, None).unwrap();
+        assert_eq!(report.videos, vec![root.join("video.mp4")]);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].path, dangling);
+        assert!(report.skipped[0].reason.starts_with("broken symlink"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks_with_loop() {
+        use std::os::unix::fs::symlink;
+
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let shows = root.join("shows");
+        let elsewhere = tempdir().unwrap();
+        fs::create_dir(&shows).unwrap();
+        File::create(shows.join("episode.mkv")).unwrap();
+        File::create(elsewhere.path().join("linked.mp4")).unwrap();
+        symlink(root, shows.join("loop")).unwrap();
+        symlink(elsewhere.path(), root.join("external")).unwrap();
+
+        // By default symlinked directories are not descended into.
+        assert_eq!(
+            find_video_files(root, &scan_options(true)).unwrap(),
+            vec![shows.join("episode.mkv")]
+        );
+
+        // Following them reaches the external directory, and the loop back to the
+        // root terminates without listing anything twice.
+        let options = ScanOptions {
+            follow_symlinks: true,
+            ..scan_options(true)
+        };
+        assert_eq!(
+            find_video_files(root, &options).unwrap(),
+            vec![
+                root.join("external").join("linked.mp4"),
+                shows.join("episode.mkv")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_same_filesystem_skips_other_devices() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let mount = root.join("mount");
+        fs::create_dir(&mount).unwrap();
+        File::create(root.join("local.mp4")).unwrap();
+        File::create(mount.join("remote.mp4")).unwrap();
+
+        // Pretend `mount` lives on a different device than the root.
+        let list = |dir: &Path| {
+            let mut listing = read_directory_listing(dir)?;
+            let device = if dir == mount { 2 } else { 1 };
+            listing.identity = Some(DirIdentity::Inode {
+                device,
+                inode: dir.components().count() as u64,
+            });
+            Ok(listing)
+        };
+
+        let report = collect_video_files(root, &scan_options(true), None, list).unwrap();
+        assert_eq!(report.videos.len(), 2);
+
+        let options = ScanOptions {
+            same_filesystem: true,
+            ..scan_options(true)
+        };
+        let report = collect_video_files(root, &options, None, list).unwrap();
+        assert_eq!(report.videos, vec![root.join("local.mp4")]);
+    }
+
+    #[test]
+    fn test_merge_reports_from_several_roots() {
+        let dir = tempdir().unwrap();
+        let drive_a = dir.path().join("a");
+        let drive_b = dir.path().join("b");
+        let nested = drive_a.join("shows");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir(&drive_b).unwrap();
+        File::create(drive_a.join("movie.mp4")).unwrap();
+        File::create(nested.join("episode.mkv")).unwrap();
+        File::create(drive_b.join("clip.avi")).unwrap();
+
+        let roots = dedup_roots(vec![
+            drive_b.clone(),
+            drive_a.clone(),
+            nested.clone(),
+            drive_b.join("."),
+        ]);
+        assert_eq!(
+            roots,
+            vec![drive_b.clone(), drive_a.clone(), nested.clone()]
+        );
+
+        let reports = roots
+            .iter()
+            .map(|root| scan_video_files(root, &scan_options(true), None).unwrap());
+        let merged = ScanReport::merge(reports);
+        assert_eq!(
+            merged.videos,
+            vec![
+                drive_a.join("movie.mp4"),
+                nested.join("episode.mkv"),
+                drive_b.join("clip.avi"),
+            ]
+        );
+
+        assert_eq!(
+            root_of(&drive_b.join("clip.avi"), &roots),
+            Some(drive_b.as_path())
+        );
+        assert_eq!(
+            root_of(&nested.join("episode.mkv"), &roots),
+            Some(nested.as_path())
+        );
+        assert_eq!(
+            root_of(&drive_a.join("movie.mp4"), &roots),
+            Some(drive_a.as_path())
+        );
+        assert_eq!(root_of(Path::new("/elsewhere/x.mp4"), &roots), None);
+    }
+
+    #[test]
+    fn test_parallel_scan_matches_single_threaded_and_reports_progress() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let mut expected = Vec::new();
+        for show in 0..6 {
+            for season in 0..4 {
+                let season_dir = root
+                    .join(format!("show{}", show))
+                    .join(format!("s{}", season));
+                fs::create_dir_all(&season_dir).unwrap();
+                for episode in 0..3 {
+                    let path = season_dir.join(format!("e{}.mkv", episode));
+                    File::create(&path).unwrap();
+                    expected.push(path);
+                }
+                File::create(season_dir.join("notes.txt")).unwrap();
+            }
+        }
+        expected.sort();
+
+        let single = ScanOptions {
+            threads: 1,
+            ..scan_options(true)
+        };
+        let parallel = ScanOptions {
+            threads: 8,
+            ..scan_options(true)
+        };
+        let progress = ScanProgress::default();
+        let report = scan_video_files(root, &parallel, Some(&progress)).unwrap();
+        assert_eq!(report.videos, expected);
+        assert_eq!(find_video_files(root, &single).unwrap(), expected);
+        assert_eq!(progress.directories_visited(), 1 + 6 + 6 * 4);
+        assert_eq!(progress.videos_found(), expected.len());
+    }
+
     #[test]
     fn test_find_video_files_invalid_dir() {
         let dir = tempdir().unwrap();
         let file_path = dir.path().join("file.txt");
         File::create(&file_path).unwrap();
 
-        let result = find_video_files(&file_path, true);
+        let result = find_video_files(&file_path, &scan_options(true));
         assert!(result.is_err());
     }
 }
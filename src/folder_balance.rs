@@ -0,0 +1,209 @@
+// src/folder_balance.rs
+
+use crate::file_utils::root_of;
+use crate::selection_strategy::{InverseCountWeighting, SelectionStrategy};
+use crate::video_entry::VideoEntry;
+use chrono::{DateTime, Utc};
+use rand::prelude::*;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+};
+
+/// Picks a folder first and then a video inside it, so a folder holding a long series
+/// is as likely to come up as a folder holding a handful of movies.
+///
+/// Videos are grouped by the folder `depth` levels below their library root (depth 0
+/// treats each root as one folder; videos shallower than `depth` belong to their own
+/// folder). Each folder is weighted by `1 / (picks from the folder + 1)`, multiplied
+/// by its override from `folder_weights`, and the video is then chosen by inverse-count
+/// weighting within the folder. Nothing is picked if every folder is weighted 0.
+#[derive(Debug)]
+pub struct FolderBalanced {
+    depth: usize,
+    folder_weights: HashMap<PathBuf, f64>,
+    roots: Vec<PathBuf>,
+}
+
+impl FolderBalanced {
+    /// Creates the strategy.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - How many levels below the library root a folder is taken.
+    /// * `folder_weights` - Weight multipliers for folders. An override applies to the
+    ///   folder itself and everything below it; the most specific override wins.
+    ///   A multiplier of 0 excludes the folder.
+    pub fn new(depth: usize, folder_weights: HashMap<PathBuf, f64>) -> Self {
+        Self {
+            depth,
+            folder_weights,
+            roots: Vec::new(),
+        }
+    }
+
+    /// Returns the folder `path` is grouped under.
+    fn folder_of(&self, path: &Path) -> PathBuf {
+        let parent = path.parent().unwrap_or(path);
+        let Some(root) = root_of(path, &self.roots) else {
+            // Unknown root (the library was not reported): fall back to the parent folder.
+            return parent.to_path_buf();
+        };
+        let relative = parent.strip_prefix(root).unwrap_or(Path::new(""));
+        root.join(relative.components().take(self.depth).collect::<PathBuf>())
+    }
+
+    /// Returns the weight multiplier for `folder`, 1.0 if it has no override.
+    fn multiplier(&self, folder: &Path) -> f64 {
+        self.folder_weights
+            .iter()
+            .filter(|(configured, _)| folder.starts_with(configured))
+            .max_by_key(|(configured, _)| configured.components().count())
+            .map_or(1.0, |(_, weight)| *weight)
+    }
+}
+
+impl SelectionStrategy for FolderBalanced {
+    fn library_changed(&mut self, roots: &[PathBuf], _videos: &[PathBuf]) {
+        self.roots = roots.to_vec();
+    }
+
+    fn select<'a>(
+        &mut self,
+        entries: &'a [VideoEntry],
+        now: DateTime<Utc>,
+        rng: &mut dyn Rng,
+    ) -> Option<&'a VideoEntry> {
+        // A BTreeMap keeps the folder order, and so seeded picks, stable.
+        let mut folders: BTreeMap<PathBuf, Vec<VideoEntry>> = BTreeMap::new();
+        for entry in entries {
+            folders
+                .entry(self.folder_of(&entry.path))
+                .or_default()
+                .push(entry.clone());
+        }
+        let folders: Vec<(PathBuf, Vec<VideoEntry>)> = folders.into_iter().collect();
+
+        let chosen = folders.choose_weighted(rng, |(folder, videos)| {
+            let picks: usize = videos.iter().map(|video| video.pick_count).sum();
+            self.multiplier(folder).max(0.0) / (picks as f64 + 1.0)
+        });
+        let videos = match chosen {
+            Ok((folder, videos)) => {
+                log::debug!(
+                    "Picked folder '{}' ({} videos)",
+                    folder.display(),
+                    videos.len()
+                );
+                videos
+            }
+            Err(e) => {
+                // Every candidate is in a folder weighted 0, which must not be picked anyway
+                if !entries.is_empty() {
+                    log::warn!("No folder could be picked ({:?}); every folder is excluded.", e);
+                }
+                return None;
+            }
+        };
+
+        let video = InverseCountWeighting.select(videos, now, rng)?;
+        entries.iter().find(|entry| entry.path == video.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+
+    fn entries(paths: &[PathBuf]) -> Vec<VideoEntry> {
+        paths
+            .iter()
+            .map(|path| VideoEntry::new(path.clone(), 0))
+            .collect()
+    }
+
+    /// A library with a 200-episode series and 10 movies.
+    fn library(root: &Path) -> Vec<PathBuf> {
+        let series = (0..200).map(|i| root.join("Series/Season 1").join(format!("{}.mkv", i)));
+        let movies = (0..10).map(|i| root.join("Movies").join(format!("{}.mp4", i)));
+        series.chain(movies).collect()
+    }
+
+    fn movie_share(strategy: &mut FolderBalanced, candidates: &[VideoEntry]) -> usize {
+        let mut rng = StdRng::seed_from_u64(8);
+        (0..1000)
+            .filter(|_| {
+                let entry = strategy.select(candidates, Utc::now(), &mut rng).unwrap();
+                entry.path.to_string_lossy().contains("Movies")
+            })
+            .count()
+    }
+
+    #[test]
+    fn test_folder_of_respects_depth() {
+        let root = PathBuf::from("/videos");
+        let episode = root.join("Series/Season 1/e1.mkv");
+        let top_level = root.join("clip.mp4");
+        let mut strategy = FolderBalanced::new(1, HashMap::new());
+        strategy.library_changed(std::slice::from_ref(&root), &[]);
+        assert_eq!(strategy.folder_of(&episode), root.join("Series"));
+        assert_eq!(strategy.folder_of(&top_level), root);
+
+        strategy.depth = 2;
+        assert_eq!(strategy.folder_of(&episode), root.join("Series/Season 1"));
+        strategy.depth = 0;
+        assert_eq!(strategy.folder_of(&episode), root);
+    }
+
+    #[test]
+    fn test_large_folder_does_not_dominate() {
+        let root = PathBuf::from("/videos");
+        let videos = library(&root);
+        let candidates = entries(&videos);
+        let mut strategy = FolderBalanced::new(1, HashMap::new());
+        strategy.library_changed(std::slice::from_ref(&root), &videos);
+        // Flat weighting would pick a movie about 5% of the time; here it is about half.
+        let movies = movie_share(&mut strategy, &candidates);
+        assert!(
+            (400..600).contains(&movies),
+            "movies picked {} times",
+            movies
+        );
+    }
+
+    #[test]
+    fn test_folder_weight_overrides() {
+        let root = PathBuf::from("/videos");
+        let videos = library(&root);
+        let candidates = entries(&videos);
+
+        let weights = HashMap::from([(root.join("Movies"), 3.0)]);
+        let mut strategy = FolderBalanced::new(1, weights);
+        strategy.library_changed(std::slice::from_ref(&root), &videos);
+        let movies = movie_share(&mut strategy, &candidates);
+        assert!(
+            (680..820).contains(&movies),
+            "movies picked {} times",
+            movies
+        );
+
+        // An override on a parent folder applies to the folders below it.
+        let weights = HashMap::from([(root.join("Series"), 0.0)]);
+        let mut strategy = FolderBalanced::new(2, weights);
+        strategy.library_changed(std::slice::from_ref(&root), &videos);
+        assert_eq!(movie_share(&mut strategy, &candidates), 1000);
+    }
+
+    #[test]
+    fn test_all_folders_excluded() {
+        let root = PathBuf::from("/videos");
+        let videos = library(&root);
+        let candidates = entries(&videos);
+        let weights = HashMap::from([(root.join("Series"), 0.0), (root.join("Movies"), 0.0)]);
+        let mut strategy = FolderBalanced::new(1, weights);
+        strategy.library_changed(std::slice::from_ref(&root), &videos);
+        let mut rng = StdRng::seed_from_u64(8);
+        assert!(strategy.select(&candidates, Utc::now(), &mut rng).is_none());
+    }
+}
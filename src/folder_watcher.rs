@@ -0,0 +1,357 @@
+// src/folder_watcher.rs
+
+use crate::file_utils::{find_video_files, ScanOptions};
+use notify::{
+    event::{ModifyKind, RenameMode},
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+// Type alias for the scanned video list shared between the main loop and the watcher.
+pub type SharedVideoList = Arc<Mutex<Vec<PathBuf>>>;
+
+/// Watches a scanned folder and keeps its shared video list in sync with the filesystem.
+///
+/// Created, deleted and renamed video files are added to or removed from the list as the
+/// events arrive, so the next selection sees the real folder contents without a rescan.
+/// Watching stops when the `FolderWatcher` is dropped.
+///
+/// Note that network filesystems (SMB/NFS) usually do not deliver change notifications
+/// for modifications made by other machines; "Rescan current folder" still covers that case.
+pub struct FolderWatcher {
+    // Held only to keep the OS watch alive.
+    _watcher: RecommendedWatcher,
+}
+
+impl FolderWatcher {
+    /// Starts watching `root` and applying changes to `videos`.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The scanned folder to watch.
+    /// * `options` - The options the folder was scanned with; new paths are filtered the same way.
+    /// * `videos` - The sorted video list to keep up to date.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the platform watcher cannot be created or the folder cannot be
+    /// watched (e.g. when the inotify watch limit is exhausted).
+    pub fn start(
+        root: &Path,
+        options: &ScanOptions,
+        videos: SharedVideoList,
+    ) -> notify::Result<Self> {
+        let watched_root = root.to_path_buf();
+        let watched_options = options.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+                Ok(event) => {
+                    if let Ok(mut guard) = videos.lock() {
+                        apply_event(&mut guard, &event, &watched_root, &watched_options);
+                    }
+                }
+                Err(e) => log::warn!("Folder watcher error: {}", e),
+            })?;
+
+        let mode = if options.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+        watcher.watch(root, mode)?;
+        log::debug!("Watching '{}' for changes.", root.display());
+
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+/// Inserts a path into the sorted list if it is not already present.
+fn insert_sorted(videos: &mut Vec<PathBuf>, path: PathBuf) {
+    if let Err(pos) = videos.binary_search(&path) {
+        videos.insert(pos, path);
+    }
+}
+
+/// Handles a path that appeared: a video file, or (when recursive) a whole directory.
+/// Paths excluded by the scan filter or a `.pickerignore` file are skipped.
+fn add_path(videos: &mut Vec<PathBuf>, path: &Path, root: &Path, options: &ScanOptions) {
+    let allowed = |path: &Path| {
+        path.strip_prefix(root)
+            .is_ok_and(|relative| options.allows_location(relative))
+            && options.filter.allows_path(root, path)
+    };
+    if !allowed(path) {
+        return;
+    }
+    if path.is_dir() {
+        if path.is_symlink() && !options.follow_symlinks {
+            return;
+        }
+        // A directory moved or copied in may already contain videos, and files created
+        // before the watch on it was registered produce no events, so scan it directly.
+        if options.recursive {
+            match find_video_files(path, options) {
+                Ok(found) => found
+                    .into_iter()
+                    .filter(|video| allowed(video))
+                    .for_each(|video| insert_sorted(videos, video)),
+                Err(e) => log::warn!("Could not scan new directory '{}': {}", path.display(), e),
+            }
+        }
+    } else if path.is_file() && options.is_video_file(path) {
+        insert_sorted(videos, path.to_path_buf());
+    }
+}
+
+/// Handles a path that disappeared: the file itself, or everything under a removed directory.
+fn remove_path(videos: &mut Vec<PathBuf>, path: &Path) {
+    videos.retain(|video| !video.starts_with(path));
+}
+
+/// Applies a single filesystem event to the sorted video list.
+fn apply_event(videos: &mut Vec<PathBuf>, event: &Event, root: &Path, options: &ScanOptions) {
+    match event.kind {
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            for path in &event.paths {
+                add_path(videos, path, root, options);
+            }
+        }
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            for path in &event.paths {
+                remove_path(videos, path);
+            }
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            // Paths are [from, to].
+            if let [from, to] = event.paths.as_slice() {
+                remove_path(videos, from);
+                add_path(videos, to, root, options);
+            }
+        }
+        EventKind::Modify(ModifyKind::Name(_)) => {
+            // Backends that cannot tell which side of a rename this is: check the disk.
+            for path in &event.paths {
+                if path.exists() {
+                    add_path(videos, path, root, options);
+                } else {
+                    remove_path(videos, path);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, RemoveKind};
+    use std::fs::{self, File};
+    use std::time::{Duration, Instant};
+    use tempfile::tempdir;
+
+    fn recursive() -> ScanOptions {
+        ScanOptions {
+            recursive: true,
+            ..Default::default()
+        }
+    }
+
+    fn event(kind: EventKind, paths: &[&Path]) -> Event {
+        paths.iter().fold(Event::new(kind), |event, path| {
+            event.add_path(path.to_path_buf())
+        })
+    }
+
+    #[test]
+    fn test_create_and_remove_events() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let video = root.join("b.mp4");
+        let other = root.join("a.mkv");
+        let text = root.join("notes.txt");
+        File::create(&video).unwrap();
+        File::create(&other).unwrap();
+        File::create(&text).unwrap();
+
+        let mut videos = Vec::new();
+        apply_event(
+            &mut videos,
+            &event(EventKind::Create(CreateKind::File), &[&video]),
+            root,
+            &recursive(),
+        );
+        apply_event(
+            &mut videos,
+            &event(EventKind::Create(CreateKind::File), &[&other]),
+            root,
+            &recursive(),
+        );
+        apply_event(
+            &mut videos,
+            &event(EventKind::Create(CreateKind::File), &[&text]),
+            root,
+            &recursive(),
+        );
+        // Duplicate events must not produce duplicate entries.
+        apply_event(
+            &mut videos,
+            &event(EventKind::Create(CreateKind::File), &[&video]),
+            root,
+            &recursive(),
+        );
+        assert_eq!(videos, vec![other.clone(), video.clone()]);
+
+        apply_event(
+            &mut videos,
+            &event(EventKind::Remove(RemoveKind::File), &[&video]),
+            root,
+            &recursive(),
+        );
+        assert_eq!(videos, vec![other]);
+    }
+
+    #[test]
+    fn test_rename_events() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let old = root.join("old.mp4");
+        let new = root.join("new.mp4");
+        File::create(&new).unwrap();
+
+        let mut videos = vec![old.clone()];
+        apply_event(
+            &mut videos,
+            &event(
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+                &[&old, &new],
+            ),
+            root,
+            &recursive(),
+        );
+        assert_eq!(videos, vec![new.clone()]);
+
+        // Renaming to a non-video extension drops the file from the pool.
+        let renamed = root.join("new.txt");
+        fs::rename(&new, &renamed).unwrap();
+        apply_event(
+            &mut videos,
+            &event(
+                EventKind::Modify(ModifyKind::Name(RenameMode::Any)),
+                &[&new],
+            ),
+            root,
+            &recursive(),
+        );
+        apply_event(
+            &mut videos,
+            &event(
+                EventKind::Modify(ModifyKind::Name(RenameMode::Any)),
+                &[&renamed],
+            ),
+            root,
+            &recursive(),
+        );
+        assert!(videos.is_empty());
+    }
+
+    #[test]
+    fn test_directory_events() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let season = root.join("Season 01");
+        fs::create_dir(&season).unwrap();
+        File::create(season.join("e01.mkv")).unwrap();
+        File::create(season.join("e02.mkv")).unwrap();
+
+        let mut videos = vec![root.join("movie.mp4")];
+        apply_event(
+            &mut videos,
+            &event(EventKind::Create(CreateKind::Folder), &[&season]),
+            root,
+            &recursive(),
+        );
+        assert_eq!(
+            videos,
+            vec![
+                root.join("Season 01").join("e01.mkv"),
+                root.join("Season 01").join("e02.mkv"),
+                root.join("movie.mp4"),
+            ]
+        );
+
+        apply_event(
+            &mut videos,
+            &event(EventKind::Remove(RemoveKind::Folder), &[&season]),
+            root,
+            &recursive(),
+        );
+        assert_eq!(videos, vec![root.join("movie.mp4")]);
+
+        // Non-recursive scans ignore new subdirectories.
+        apply_event(
+            &mut videos,
+            &event(EventKind::Create(CreateKind::Folder), &[&season]),
+            root,
+            &ScanOptions::default(),
+        );
+        assert_eq!(videos, vec![root.join("movie.mp4")]);
+    }
+
+    #[test]
+    fn test_filtered_paths_are_not_added() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let extras = root.join("Extras");
+        fs::create_dir(&extras).unwrap();
+        File::create(extras.join("bonus.mkv")).unwrap();
+        File::create(root.join("sample.mkv")).unwrap();
+        let options = ScanOptions {
+            recursive: true,
+            filter: crate::scan_filter::ScanFilter::new(
+                &[],
+                &["Extras".to_string(), "sample.*".to_string()],
+            )
+            .unwrap(),
+            ..Default::default()
+        };
+
+        let mut videos = Vec::new();
+        apply_event(
+            &mut videos,
+            &event(EventKind::Create(CreateKind::Folder), &[&extras]),
+            root,
+            &options,
+        );
+        apply_event(
+            &mut videos,
+            &event(
+                EventKind::Create(CreateKind::File),
+                &[&root.join("sample.mkv")],
+            ),
+            root,
+            &options,
+        );
+        assert!(videos.is_empty());
+    }
+
+    #[test]
+    fn test_watcher_tracks_new_files() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let videos: SharedVideoList = Arc::new(Mutex::new(Vec::new()));
+        let _watcher = FolderWatcher::start(root, &recursive(), videos.clone()).unwrap();
+
+        let video = root.join("episode.mkv");
+        File::create(&video).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline && videos.lock().unwrap().is_empty() {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(*videos.lock().unwrap(), vec![video]);
+    }
+}
@@ -1,12 +1,17 @@
 // src/history_manager.rs
 
-use crate::file_utils::get_history_path;
-use chrono::{DateTime, Utc};
+use crate::config::HISTORY_COMPACTION_THRESHOLD;
+use crate::file_utils::{atomic_write, get_history_path};
+use crate::retention::{PrunePlan, RetentionPolicy};
+use chrono::{DateTime, Local, Utc};
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::{File, OpenOptions},
-    io::{self, BufReader, BufWriter},
-    path::Path,
+    collections::HashMap,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    thread,
 };
 
 /// Represents an entry in the video picking history log.
@@ -16,95 +21,571 @@ pub struct HistoryEntry {
     pub path: String,
     /// The UTC timestamp indicating when the video was picked.
     pub picked_at: DateTime<Utc>,
+    /// The 1–5 star rating given after this pick, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<u8>,
+    /// How many picks the entry stands for. More than one for picks collapsed by the
+    /// retention policy, in which case `picked_at` is the latest of them.
+    #[serde(default = "single_pick", skip_serializing_if = "is_single_pick")]
+    pub count: usize,
 }
 
-/// Loads the video picking history from the JSON file.
+fn single_pick() -> usize {
+    1
+}
+
+fn is_single_pick(count: &usize) -> bool {
+    *count == 1
+}
+
+/// One line of the history log. The log is a JSON-lines file that only ever grows by
+/// appending a line, oldest first; compaction rewrites it with each rating folded into
+/// its pick.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+enum LogRecord {
+    /// A rating given after an earlier pick, identified by the pick's path and time.
+    Rating {
+        rated: String,
+        picked_at: DateTime<Utc>,
+        rating: u8,
+    },
+    /// A pick, including its rating if the log has been compacted since.
+    Pick(HistoryEntry),
+}
+
+/// What `read_history` found out about the history file besides its entries.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct LogState {
+    /// The file is still a single JSON array, as written by older versions.
+    is_array: bool,
+    /// The log holds enough rating records, out-of-order picks or partial lines that
+    /// rewriting it is worthwhile.
+    needs_compaction: bool,
+    /// The end of the log as read.
+    end: LogPosition,
+}
+
+/// How much of the history log an in-memory history reflects, so that records appended
+/// by other instances of the picker since can be merged in without reading the whole log.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogPosition {
+    /// The length of the log when it was last read or appended to; 0 if unknown.
+    offset: u64,
+    /// The log's last line at that point. If the bytes before `offset` no longer match
+    /// it, the log has been rewritten (compacted or pruned) since and is read in full.
+    last_line: Vec<u8>,
+}
+
+impl LogPosition {
+    /// The position after `contents`, read from the log at `start`.
+    fn after(start: u64, contents: &[u8]) -> Self {
+        let line_start = contents[..contents.len().saturating_sub(1)]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        Self {
+            offset: start + contents.len() as u64,
+            last_line: contents[line_start..].to_vec(),
+        }
+    }
+}
+
+/// Returned by `load_history` when the history file could not be parsed. The file has
+/// been moved aside to `backup_path`, so saving a new history cannot overwrite it.
+#[derive(Debug)]
+pub struct CorruptHistoryError {
+    /// Where the unreadable history file was moved.
+    pub backup_path: PathBuf,
+    source: serde_json::Error,
+}
+
+impl fmt::Display for CorruptHistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the history file could not be read ({}); it was backed up to '{}'",
+            self.source,
+            self.backup_path.display()
+        )
+    }
+}
+
+impl std::error::Error for CorruptHistoryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Moves an unreadable history file aside, to e.g. `history.json.corrupt-20240101-120000`.
+fn back_up_corrupt_file(history_path: &Path) -> io::Result<PathBuf> {
+    let mut backup_name = history_path.file_name().unwrap_or_default().to_os_string();
+    backup_name.push(format!(".corrupt-{}", Local::now().format("%Y%m%d-%H%M%S")));
+    let backup_path = history_path.with_file_name(backup_name);
+    fs::rename(history_path, &backup_path)?;
+    Ok(backup_path)
+}
+
+/// Loads the video picking history, sorted newest first, and the position in the log it
+/// was read up to; pass that to `add_to_history` and `rate_latest_pick`.
 /// If `custom_path` is provided, it uses that file instead of the default history file.
 /// If the file doesn't exist, an empty `Vec` is returned.
-/// If the file exists but cannot be parsed, a warning is logged, and an empty `Vec` is returned.
+///
+/// Both the append-only JSON-lines log and the single JSON array written by older
+/// versions are accepted. An array file is converted to the log format on the spot, so
+/// this happens once. If the log has accumulated enough rating records to be worth
+/// compacting, a background thread rewrites it after the history is returned.
 ///
 /// # Errors
 ///
 /// Returns an error if the history file path cannot be determined or if an
 /// I/O error (other than `NotFound`) occurs while reading the file.
-pub fn load_history(custom_path: Option<&Path>) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
-    let history_path_buf;
-    let history_path = match custom_path {
-        Some(p) => p,
-        None => {
-            history_path_buf = get_history_path()?;
-            &history_path_buf
-        }
-    };
-
-    match File::open(history_path) {
-        Ok(file) => {
-            let reader = BufReader::new(file);
-            match serde_json::from_reader(reader) {
-                Ok(history) => Ok(history),
-                Err(e) => {
-                    eprintln!(
-                        "Warning: Could not parse history file at '{}' (Error: {}). Starting with empty history.",
-                        history_path.display(), e
-                    );
-                    Ok(Vec::new()) // Return empty history on parsing error.
-                }
-            }
-        }
-        Err(e) if e.kind() == io::ErrorKind::NotFound => {
-            Ok(Vec::new()) // File not found is not an error; return empty history.
-        }
-        Err(e) => {
-            Err(Box::new(e)) // Propagate other I/O errors.
-        }
+/// If the file exists but cannot be parsed, it is renamed with a timestamp suffix and a
+/// `CorruptHistoryError` naming the backup is returned; the caller decides whether to
+/// continue with an empty history.
+pub fn load_history(
+    custom_path: Option<&Path>,
+) -> Result<(Vec<HistoryEntry>, LogPosition), Box<dyn std::error::Error>> {
+    let history_path = resolve_history_path(custom_path)?;
+    let _lock = lock_history(&history_path)?;
+    let (history, state) = read_history(&history_path)?;
+    if state.is_array {
+        convert_to_log(&history, &history_path)?;
+        return Ok((history, LogPosition::default()));
+    } else if state.needs_compaction {
+        spawn_compaction(history_path); // Waits for `_lock` to be released.
     }
+    Ok((history, state.end))
 }
 
-/// Adds a video file path to the history and saves the updated history to disk.
+/// Adds a video file path to the history and appends it to the history log on disk.
 /// The history is maintained in sorted order by timestamp (most recent first).
 /// If `custom_path` is provided, it saves to that file instead of the default history file.
 ///
+/// Saving a pick appends a single line under the history lock, so it takes the same
+/// time however long the history is, and never overwrites picks saved by other
+/// instances of the picker since `history` was loaded. Those picks (and ratings) are
+/// merged into `history` first.
+///
 /// # Arguments
 ///
 /// * `history` - A mutable reference to the current vector of history entries.
+/// * `position` - How far into the log `history` reflects; moved to the end of the log.
 /// * `file_path` - The path of the video file to be added to the history.
 ///
+/// Returns the new entry.
+///
 /// # Errors
 ///
-/// Returns an error if the history file path cannot be determined, or if
-/// I/O or serialization errors occur during the saving process.
+/// Returns an error if the history file path cannot be determined, if the history
+/// cannot be locked, or if I/O or serialization errors occur during the saving process.
 pub fn add_to_history(
     history: &mut Vec<HistoryEntry>,
+    position: &mut LogPosition,
     file_path: &Path,
     custom_path: Option<&Path>
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<HistoryEntry, Box<dyn std::error::Error>> {
+    let history_path = resolve_history_path(custom_path)?;
+    let _lock = lock_history(&history_path)?;
+    catch_up(&history_path, history, position)?;
+
     let entry = HistoryEntry {
         path: file_path.to_string_lossy().into_owned(), // Handle potentially non-UTF8 paths.
         picked_at: Utc::now(),
+        rating: None,
+        count: 1,
     };
-    history.push(entry);
-    history.sort_by(|a, b| b.picked_at.cmp(&a.picked_at)); // Sort by timestamp, descending.
-
-    let history_path_buf;
-    let history_path = match custom_path {
-        Some(p) => p,
-        None => {
-            history_path_buf = get_history_path()?;
-            &history_path_buf
-        }
+    append_to_log(&history_path, &LogRecord::Pick(entry.clone()), history, position)?;
+
+    // Normally the new pick goes first; this keeps the order if the clock went back.
+    let position = history.partition_point(|existing| existing.picked_at > entry.picked_at);
+    history.insert(position, entry.clone());
+    Ok(entry)
+}
+
+/// Rates the most recent pick of a video, appending the rating to the history log.
+/// If `custom_path` is provided, it saves to that file instead of the default history file.
+/// Picks and ratings saved by other instances are merged into `history` first.
+///
+/// # Arguments
+///
+/// * `history` - The history, sorted newest first.
+/// * `position` - How far into the log `history` reflects; moved to the end of the log.
+/// * `file_path` - The path of the rated video.
+/// * `rating` - The rating, from 1 to 5 stars.
+///
+/// Returns false (and saves nothing) if the video is not in `history`.
+///
+/// # Errors
+///
+/// Returns an error if the history file path cannot be determined, if the history
+/// cannot be locked, or if I/O or serialization errors occur during the saving process.
+pub fn rate_latest_pick(
+    history: &mut Vec<HistoryEntry>,
+    position: &mut LogPosition,
+    file_path: &Path,
+    rating: u8,
+    custom_path: Option<&Path>
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let history_path = resolve_history_path(custom_path)?;
+    let _lock = lock_history(&history_path)?;
+    catch_up(&history_path, history, position)?;
+
+    let path = file_path.to_string_lossy();
+    let Some(index) = history.iter().position(|entry| entry.path == path) else {
+        return Ok(false);
+    };
+    let rating = rating.clamp(1, 5);
+    let record = LogRecord::Rating {
+        rated: history[index].path.clone(),
+        picked_at: history[index].picked_at,
+        rating,
     };
+    append_to_log(&history_path, &record, history, position)?;
+    history[index].rating = Some(rating);
+    Ok(true)
+}
 
-    let file = OpenOptions::new()
+/// Returns each video's current rating: the one given after its most recent rated pick.
+///
+/// # Arguments
+///
+/// * `history` - The history, sorted newest first.
+pub fn latest_ratings(history: &[HistoryEntry]) -> HashMap<&str, u8> {
+    let mut ratings = HashMap::new();
+    for entry in history {
+        if let Some(rating) = entry.rating {
+            ratings.entry(entry.path.as_str()).or_insert(rating);
+        }
+    }
+    ratings
+}
+
+/// Returns `custom_path`, or the default history file path if it is `None`.
+fn resolve_history_path(custom_path: Option<&Path>) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    match custom_path {
+        Some(p) => Ok(p.to_path_buf()),
+        None => get_history_path(),
+    }
+}
+
+/// Takes the advisory lock that serialises history access between picker instances.
+/// The lock is held on a separate `<history file>.lock` file, because compaction
+/// replaces the history file itself. It is released when the returned file is dropped.
+fn lock_history(history_path: &Path) -> io::Result<File> {
+    let mut lock_name = history_path.file_name().unwrap_or_default().to_os_string();
+    lock_name.push(".lock");
+    let lock_file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
         .write(true)
+        .open(history_path.with_file_name(lock_name))?;
+    lock_file.lock()?;
+    Ok(lock_file)
+}
+
+/// Reads the history file in either format, sorted newest first. The caller must hold
+/// the history lock. A missing file is an empty history; an unparsable one is backed up
+/// (see `load_history`).
+fn read_history(
+    history_path: &Path,
+) -> Result<(Vec<HistoryEntry>, LogState), Box<dyn std::error::Error>> {
+    let contents = match fs::read_to_string(history_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            // File not found is not an error; return empty history.
+            return Ok((Vec::new(), LogState::default()));
+        }
+        Err(e) => return Err(Box::new(e)), // Propagate other I/O errors.
+    };
+
+    let parsed = if contents.trim_start().starts_with('[') {
+        let state = LogState { is_array: true, ..LogState::default() };
+        serde_json::from_str(&contents).map(|history| (history, state))
+    } else {
+        parse_log(&contents).map(|(history, state)| {
+            (history, LogState { end: LogPosition::after(0, contents.as_bytes()), ..state })
+        })
+    };
+    match parsed {
+        Ok((mut history, state)) => {
+            history.sort_by_key(|entry| std::cmp::Reverse(entry.picked_at));
+            Ok((history, state))
+        }
+        Err(source) => {
+            // Never let the next save overwrite the only copy of the old history.
+            let backup_path = back_up_corrupt_file(history_path)?;
+            log::warn!(
+                "Could not parse history file at '{}' ({}). Moved it to '{}'.",
+                history_path.display(), source, backup_path.display()
+            );
+            Err(Box::new(CorruptHistoryError { backup_path, source }))
+        }
+    }
+}
+
+/// Parses a JSON-lines history log, applying each rating record to its pick.
+/// A partial last line, left by a crash in the middle of an append, is skipped.
+fn parse_log(contents: &str) -> Result<(Vec<HistoryEntry>, LogState), serde_json::Error> {
+    let mut history: Vec<HistoryEntry> = Vec::new();
+    let mut ratings = Vec::new();
+    let mut state = LogState::default();
+    let mut lines = contents.lines().filter(|line| !line.trim().is_empty()).peekable();
+    while let Some(line) = lines.next() {
+        match serde_json::from_str(line) {
+            Ok(LogRecord::Pick(entry)) => {
+                state.needs_compaction |= history
+                    .last()
+                    .is_some_and(|previous| previous.picked_at > entry.picked_at);
+                history.push(entry);
+            }
+            Ok(LogRecord::Rating { rated, picked_at, rating }) => {
+                ratings.push((rated, picked_at, rating));
+            }
+            Err(e) if lines.peek().is_none() && !contents.ends_with('\n') => {
+                log::warn!("Ignoring an incomplete last line in the history log ({}).", e);
+                state.needs_compaction = true;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    state.needs_compaction |= ratings.len() >= HISTORY_COMPACTION_THRESHOLD;
+    for (rated, picked_at, rating) in ratings {
+        // Ratings follow their pick closely, so search from the end.
+        let pick = history
+            .iter_mut()
+            .rev()
+            .find(|entry| entry.path == rated && entry.picked_at == picked_at);
+        if let Some(entry) = pick {
+            entry.rating = Some(rating);
+        }
+    }
+    Ok((history, state))
+}
+
+/// Brings `history` up to date with the log before a record is appended to it: records
+/// appended since `position` (by other instances) are merged in, or if the log has been
+/// rewritten since, it is read again in full. `position` is moved to the end of the log.
+/// The caller must hold the history lock.
+///
+/// Should the log have become corrupt, it is backed up and the log starts over from
+/// `history`.
+fn catch_up(
+    history_path: &Path,
+    history: &mut Vec<HistoryEntry>,
+    position: &mut LogPosition,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut file = match File::open(history_path) {
+        Ok(file) => file,
+        // Nothing to merge; appending starts a new log.
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(Box::new(e)),
+    };
+    let len = file.metadata()?.len();
+    if position.offset > 0 && len >= position.offset {
+        let mut known_line = vec![0; position.last_line.len()];
+        file.seek(SeekFrom::Start(position.offset - known_line.len() as u64))?;
+        file.read_exact(&mut known_line)?;
+        if known_line == position.last_line {
+            let mut appended = Vec::new();
+            file.read_to_end(&mut appended)?;
+            if !appended.is_empty() {
+                merge_appended(history, &appended);
+                *position = LogPosition::after(position.offset, &appended);
+            }
+            return Ok(());
+        }
+    }
+
+    match read_history(history_path) {
+        Ok((on_disk, state)) => {
+            *history = on_disk;
+            *position = state.end;
+        }
+        Err(e) if e.is::<CorruptHistoryError>() => {
+            log::warn!("{}. Saving the history of this session instead.", e);
+            write_log(history, history_path)?;
+            *position = LogPosition::default();
+        }
+        Err(e) => return Err(e),
+    }
+    Ok(())
+}
+
+/// Merges log records appended by other instances into `history`, sorted newest first.
+/// An incomplete last line is skipped; appending cuts it off.
+fn merge_appended(history: &mut Vec<HistoryEntry>, appended: &[u8]) {
+    let lines = appended.split(|&b| b == b'\n').filter(|line| !line.trim_ascii().is_empty());
+    for line in lines {
+        match serde_json::from_slice(line) {
+            Ok(LogRecord::Pick(entry)) => {
+                let index =
+                    history.partition_point(|existing| existing.picked_at > entry.picked_at);
+                history.insert(index, entry);
+            }
+            Ok(LogRecord::Rating { rated, picked_at, rating }) => {
+                let pick = history
+                    .iter_mut()
+                    .find(|entry| entry.path == rated && entry.picked_at == picked_at);
+                if let Some(entry) = pick {
+                    entry.rating = Some(rating);
+                }
+            }
+            Err(e) => log::debug!("Skipping an unreadable line in the history log ({}).", e),
+        }
+    }
+}
+
+/// Returns true if the history file is a JSON array written by an older version.
+fn is_array_file(history_path: &Path) -> io::Result<bool> {
+    let mut start = [0; 64];
+    let read = match File::open(history_path) {
+        Ok(mut file) => file.read(&mut start)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    Ok(start[..read].trim_ascii_start().starts_with(b"["))
+}
+
+/// Appends one record to the history log, flushes it to disk and moves `position` past
+/// it. `history` must have been brought up to date with `catch_up`, and the caller must
+/// hold the history lock.
+///
+/// If the file is still an array (e.g. an older version rewrote it), it is converted to
+/// the log format from `history` first.
+fn append_to_log(
+    history_path: &Path,
+    record: &LogRecord,
+    history: &[HistoryEntry],
+    position: &mut LogPosition,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if is_array_file(history_path)? {
+        convert_to_log(history, history_path)?;
+    }
+
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
         .create(true)
-        .truncate(true)
         .open(history_path)?;
-    let writer = BufWriter::new(file); // Use BufWriter for potentially better I/O performance.
-    serde_json::to_writer_pretty(writer, history)?; // Use pretty printing for readability.
+    end_last_line(&mut file)?;
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    // One write per record, so a crash leaves at most a partial last line.
+    file.write_all(line.as_bytes())?;
+    file.sync_data()?;
+    *position = LogPosition {
+        offset: file.metadata()?.len(),
+        last_line: line.into_bytes(),
+    };
+    Ok(())
+}
+
+/// Makes sure the log ends with a newline before a record is appended. A crash in the
+/// middle of an append leaves a partial line, which is cut off; a complete record that
+/// merely lacks its newline (e.g. after editing the file by hand) gets one.
+fn end_last_line(file: &mut File) -> io::Result<()> {
+    if file.metadata()?.len() == 0 {
+        return Ok(());
+    }
+    let mut last_byte = [0];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last_byte)?;
+    if last_byte[0] == b'\n' {
+        return Ok(());
+    }
+
+    let mut contents = Vec::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_end(&mut contents)?;
+    let line_start = contents.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    if serde_json::from_slice::<LogRecord>(&contents[line_start..]).is_ok() {
+        file.write_all(b"\n")
+    } else {
+        log::warn!("Removing an incomplete last line from the history log.");
+        file.set_len(line_start as u64)
+    }
+}
 
+/// Rewrites the history file as a log, replacing the previous file atomically: a crash
+/// or a full disk mid-write leaves the previous file intact. `history` must be sorted
+/// newest first. The caller must hold the history lock.
+fn write_log(history: &[HistoryEntry], history_path: &Path) -> io::Result<()> {
+    atomic_write(history_path, |writer| {
+        for entry in history.iter().rev() {
+            serde_json::to_writer(&mut *writer, entry)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    })
+}
+
+/// Converts an array history file to the log format. The caller must hold the history lock.
+fn convert_to_log(history: &[HistoryEntry], history_path: &Path) -> io::Result<()> {
+    write_log(history, history_path)?;
+    log::info!(
+        "Converted the history file '{}' to the append-only format.",
+        history_path.display()
+    );
     Ok(())
 }
 
+/// Rewrites the history log with ratings folded into their picks, in time order and
+/// without partial lines. Takes the history lock.
+fn compact_history(history_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let _lock = lock_history(history_path)?;
+    let (history, _) = read_history(history_path)?;
+    write_log(&history, history_path)?;
+    log::debug!("Compacted the history log '{}'.", history_path.display());
+    Ok(())
+}
+
+/// Applies `policy` to the history file, rewriting it if anything is outside the policy.
+/// If `custom_path` is provided, it uses that file instead of the default history file.
+///
+/// Returns what was removed. The file is re-read under the history lock, so picks saved
+/// by other instances are taken into account.
+///
+/// # Errors
+///
+/// Returns an error if the history file path cannot be determined, if the history
+/// cannot be locked or read, or if the rewritten file cannot be saved.
+pub fn prune_history(
+    policy: &RetentionPolicy,
+    now: DateTime<Utc>,
+    custom_path: Option<&Path>,
+) -> Result<PrunePlan, Box<dyn std::error::Error>> {
+    let history_path = resolve_history_path(custom_path)?;
+    let _lock = lock_history(&history_path)?;
+    let (mut history, _) = read_history(&history_path)?;
+    let expired_from = policy.expired_from(&history, now);
+    let plan = policy.plan(&history[expired_from..]);
+    if !plan.is_empty() {
+        plan.apply_to(&mut history);
+        write_log(&history, &history_path)?;
+    }
+    Ok(plan)
+}
+
+/// Compacts the history log on a background thread. If the picker exits first, the
+/// atomic rewrite leaves the log as it was, to be compacted on a later start.
+fn spawn_compaction(history_path: PathBuf) {
+    let spawned = thread::Builder::new()
+        .name("history-compaction".to_string())
+        .spawn(move || {
+            if let Err(e) = compact_history(&history_path) {
+                log::warn!(
+                    "Could not compact the history log '{}': {}",
+                    history_path.display(), e
+                );
+            }
+        });
+    if let Err(e) = spawned {
+        log::warn!("Could not start compacting the history log: {}", e);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -117,16 +598,17 @@ mod tests {
         let temp_path = temp_file.path();
 
         let mut history = Vec::new();
+        let mut position = LogPosition::default();
         let video_path = PathBuf::from("/path/to/video.mp4");
 
         // Add to history
-        add_to_history(&mut history, &video_path, Some(temp_path)).unwrap();
+        add_to_history(&mut history, &mut position, &video_path, Some(temp_path)).unwrap();
 
         assert_eq!(history.len(), 1);
         assert_eq!(history[0].path, "/path/to/video.mp4");
 
         // Load history
-        let loaded_history = load_history(Some(temp_path)).unwrap();
+        let loaded_history = load_history(Some(temp_path)).unwrap().0;
         assert_eq!(loaded_history.len(), 1);
         assert_eq!(loaded_history[0].path, "/path/to/video.mp4");
         // We can't strictly compare timestamps as serialization might lose precision or time might pass,
@@ -134,6 +616,229 @@ mod tests {
         assert_eq!(history[0].picked_at, loaded_history[0].picked_at);
     }
 
+    #[test]
+    fn test_rate_latest_pick() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.path();
+        let video_path = PathBuf::from("/path/to/video.mp4");
+
+        let mut history = Vec::new();
+        let mut position = LogPosition::default();
+        assert!(
+            !rate_latest_pick(&mut history, &mut position, &video_path, 4, Some(temp_path)).unwrap()
+        );
+        add_to_history(&mut history, &mut position, &video_path, Some(temp_path)).unwrap();
+        let other_path = Path::new("/path/to/other.mp4");
+        add_to_history(&mut history, &mut position, other_path, Some(temp_path)).unwrap();
+        assert!(
+            rate_latest_pick(&mut history, &mut position, &video_path, 4, Some(temp_path)).unwrap()
+        );
+
+        let loaded_history = load_history(Some(temp_path)).unwrap().0;
+        assert_eq!(loaded_history, history);
+        assert_eq!(latest_ratings(&loaded_history), HashMap::from([("/path/to/video.mp4", 4)]));
+
+        // Entries written before ratings existed still load.
+        std::fs::write(
+            temp_path,
+            r#"[{ "path": "/old.mp4", "picked_at": "2024-01-01T00:00:00Z" }]"#,
+        )
+        .unwrap();
+        assert_eq!(load_history(Some(temp_path)).unwrap().0[0].rating, None);
+    }
+
+    #[test]
+    fn test_corrupt_history_is_backed_up() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let history_path = temp_dir.path().join("history.json");
+        std::fs::write(&history_path, r#"[{ "path": "/a.mp4", "picked_at": "2024-"#).unwrap();
+
+        let error = load_history(Some(&history_path)).unwrap_err();
+        let corrupt = error.downcast_ref::<CorruptHistoryError>().unwrap();
+        assert!(!history_path.exists());
+        assert_eq!(corrupt.backup_path.parent(), Some(temp_dir.path()));
+        assert!(corrupt
+            .backup_path
+            .to_string_lossy()
+            .contains("history.json.corrupt-"));
+        assert_eq!(
+            std::fs::read_to_string(&corrupt.backup_path).unwrap(),
+            r#"[{ "path": "/a.mp4", "picked_at": "2024-"#
+        );
+
+        // Starting over writes a fresh file next to the backup.
+        let mut history = Vec::new();
+        let mut position = LogPosition::default();
+        add_to_history(&mut history, &mut position, Path::new("/b.mp4"), Some(&history_path))
+            .unwrap();
+        assert_eq!(load_history(Some(&history_path)).unwrap().0, history);
+    }
+
+    #[test]
+    fn test_array_history_is_converted_to_log() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let history_path = temp_dir.path().join("history.json");
+        std::fs::write(
+            &history_path,
+            r#"[
+  { "path": "/new.mp4", "picked_at": "2024-01-02T00:00:00Z", "rating": 5 },
+  { "path": "/old.mp4", "picked_at": "2024-01-01T00:00:00Z" }
+]"#,
+        )
+        .unwrap();
+
+        let (mut history, mut position) = load_history(Some(&history_path)).unwrap();
+        let paths: Vec<&str> = history.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(paths, ["/new.mp4", "/old.mp4"]);
+        assert_eq!(history[0].rating, Some(5));
+        assert_eq!(
+            std::fs::read_to_string(&history_path).unwrap(),
+            concat!(
+                r#"{"path":"/old.mp4","picked_at":"2024-01-01T00:00:00Z"}"#, "\n",
+                r#"{"path":"/new.mp4","picked_at":"2024-01-02T00:00:00Z","rating":5}"#, "\n",
+            )
+        );
+
+        // From now on picks and ratings are appended to the converted log.
+        let before = std::fs::read_to_string(&history_path).unwrap();
+        add_to_history(&mut history, &mut position, Path::new("/next.mp4"), Some(&history_path))
+            .unwrap();
+        rate_latest_pick(&mut history, &mut position, Path::new("/old.mp4"), 2, Some(&history_path))
+            .unwrap();
+        let after = std::fs::read_to_string(&history_path).unwrap();
+        assert!(after.starts_with(&before));
+        assert_eq!(after.lines().count(), 4);
+        assert_eq!(load_history(Some(&history_path)).unwrap().0, history);
+    }
+
+    #[test]
+    fn test_incomplete_last_line_is_dropped() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let history_path = temp_dir.path().join("history.json");
+        let complete = r#"{"path":"/a.mp4","picked_at":"2024-01-01T00:00:00Z"}"#;
+        std::fs::write(&history_path, format!("{}\n{{\"path\":\"/b.mp", complete)).unwrap();
+
+        let (mut history, mut position) = load_history(Some(&history_path)).unwrap();
+        assert_eq!(history.len(), 1);
+
+        add_to_history(&mut history, &mut position, Path::new("/c.mp4"), Some(&history_path))
+            .unwrap();
+        let contents = std::fs::read_to_string(&history_path).unwrap();
+        assert!(contents.starts_with(&format!("{}\n{{\"path\":\"/c.mp4\"", complete)));
+        assert_eq!(load_history(Some(&history_path)).unwrap().0, history);
+    }
+
+    #[test]
+    fn test_compaction_folds_ratings_into_picks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let history_path = temp_dir.path().join("history.json");
+        let mut history = Vec::new();
+        let mut position = LogPosition::default();
+        add_to_history(&mut history, &mut position, Path::new("/a.mp4"), Some(&history_path))
+            .unwrap();
+        add_to_history(&mut history, &mut position, Path::new("/b.mp4"), Some(&history_path))
+            .unwrap();
+        for rating in 1..=5 {
+            let video = Path::new("/a.mp4");
+            rate_latest_pick(&mut history, &mut position, video, rating, Some(&history_path))
+                .unwrap();
+        }
+        let contents = std::fs::read_to_string(&history_path).unwrap();
+        let (_, state) = parse_log(&contents).unwrap();
+        assert!(!state.needs_compaction);
+        assert_eq!(contents.lines().count(), 7);
+
+        compact_history(&history_path).unwrap();
+        let contents = std::fs::read_to_string(&history_path).unwrap();
+        assert_eq!(contents.lines().count(), 2);
+        assert_eq!(load_history(Some(&history_path)).unwrap().0, history);
+        assert_eq!(latest_ratings(&history), HashMap::from([("/a.mp4", 5)]));
+
+        // Enough rating records make loading compact the log in the background.
+        let rated = format!(
+            r#"{{"rated":"/a.mp4","picked_at":"{}","rating":3}}"#,
+            history[1].picked_at.to_rfc3339()
+        );
+        let log = contents + &format!("{}\n", rated).repeat(HISTORY_COMPACTION_THRESHOLD);
+        assert!(parse_log(&log).unwrap().1.needs_compaction);
+    }
+
+    #[test]
+    fn test_picks_of_other_instances_are_merged() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let history_path = temp_dir.path().join("history.json");
+        let path = Some(history_path.as_path());
+        let (mut first, mut first_position) = load_history(path).unwrap();
+        let (mut second, mut second_position) = load_history(path).unwrap();
+
+        add_to_history(&mut first, &mut first_position, Path::new("/a.mp4"), path).unwrap();
+        add_to_history(&mut second, &mut second_position, Path::new("/b.mp4"), path).unwrap();
+        assert_eq!(second.len(), 2);
+        // The second instance can rate a pick it only knows about from the log.
+        assert!(rate_latest_pick(&mut second, &mut second_position, Path::new("/a.mp4"), 4, path)
+            .unwrap());
+        add_to_history(&mut first, &mut first_position, Path::new("/c.mp4"), path).unwrap();
+        assert_eq!(first, load_history(path).unwrap().0);
+        assert_eq!(first[2].rating, Some(4));
+
+        // After the log is rewritten, it is read again in full.
+        compact_history(&history_path).unwrap();
+        add_to_history(&mut second, &mut second_position, Path::new("/d.mp4"), path).unwrap();
+        assert_eq!(second.len(), 4);
+        assert_eq!(second, load_history(path).unwrap().0);
+    }
+
+    /// Set for the child processes spawned by `test_concurrent_instances_keep_every_pick`:
+    /// the history file to write to, and the child's number.
+    const CHILD_HISTORY_ENV: &str = "FILE_PICKER_TEST_CHILD_HISTORY";
+    const CHILD_ID_ENV: &str = "FILE_PICKER_TEST_CHILD_ID";
+
+    #[test]
+    fn test_concurrent_instances_keep_every_pick() {
+        const INSTANCES: usize = 4;
+        const PICKS: usize = 25;
+
+        if let Ok(history_path) = std::env::var(CHILD_HISTORY_ENV) {
+            // In a child: behave like a picker instance that loads the history once at
+            // startup and then saves a pick at a time.
+            let history_path = PathBuf::from(history_path);
+            let id = std::env::var(CHILD_ID_ENV).unwrap();
+            let (mut history, mut position) = load_history(Some(&history_path)).unwrap();
+            for pick in 0..PICKS {
+                let video = PathBuf::from(format!("/videos/{}-{}.mp4", id, pick));
+                add_to_history(&mut history, &mut position, &video, Some(&history_path)).unwrap();
+            }
+            return;
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let history_path = temp_dir.path().join("history.json");
+        let children: Vec<_> = (0..INSTANCES)
+            .map(|id| {
+                std::process::Command::new(std::env::current_exe().unwrap())
+                    .args([
+                        "--exact",
+                        "history_manager::tests::test_concurrent_instances_keep_every_pick",
+                        "--test-threads=1",
+                    ])
+                    .env(CHILD_HISTORY_ENV, &history_path)
+                    .env(CHILD_ID_ENV, id.to_string())
+                    .stdout(std::process::Stdio::null())
+                    .spawn()
+                    .unwrap()
+            })
+            .collect();
+        for mut child in children {
+            assert!(child.wait().unwrap().success());
+        }
+
+        let history = load_history(Some(&history_path)).unwrap().0;
+        let paths: std::collections::HashSet<&str> =
+            history.iter().map(|entry| entry.path.as_str()).collect();
+        assert_eq!(history.len(), INSTANCES * PICKS);
+        assert_eq!(paths.len(), INSTANCES * PICKS);
+    }
+
     #[test]
     fn test_load_history_empty() {
         let _temp_file = NamedTempFile::new().unwrap();
@@ -146,7 +851,7 @@ mod tests {
         let temp_dir = tempfile::tempdir().unwrap();
         let non_existent_path = temp_dir.path().join("history.json");
 
-        let history = load_history(Some(&non_existent_path)).unwrap();
+        let history = load_history(Some(&non_existent_path)).unwrap().0;
         assert!(history.is_empty());
     }
 }
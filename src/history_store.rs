@@ -0,0 +1,482 @@
+// src/history_store.rs
+
+use crate::history_manager::{
+    add_to_history, load_history, prune_history, rate_latest_pick, HistoryEntry,
+    LogPosition,
+};
+use crate::retention::{PrunePlan, RetentionPolicy};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+/// Where the pick history is kept. Chosen with `history_backend` in the config file.
+///
+/// Only the history moves: the library index stays in `library_index.json` whichever
+/// backend is chosen.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryBackend {
+    /// The append-only JSON-lines log, `history.json`, read into memory at startup.
+    #[default]
+    Json,
+    /// An SQLite database, `history.sqlite3`, queried through indexes.
+    Sqlite,
+}
+
+/// Persistent storage for the pick history.
+///
+/// Entries returned by the queries are sorted newest first, like the history everywhere
+/// else in the picker.
+pub trait HistoryStore {
+    /// Returns every pick. Only for commands that need the whole history, such as the
+    /// history view; the picker itself uses the narrower queries below.
+    fn load(&self) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>>;
+
+    /// Records a pick of `file_path` made now and returns the new entry.
+    fn append(&mut self, file_path: &Path) -> Result<HistoryEntry, Box<dyn std::error::Error>>;
+
+    /// Rates the most recent pick of `file_path` with 1 to 5 stars.
+    /// Returns false (and stores nothing) if the video has never been picked.
+    fn rate_latest_pick(
+        &mut self,
+        file_path: &Path,
+        rating: u8,
+    ) -> Result<bool, Box<dyn std::error::Error>>;
+
+    /// Returns how many times each path has been picked, including picks collapsed by the
+    /// retention policy. Paths never picked are absent.
+    fn pick_counts(&self) -> Result<HashMap<String, usize>, Box<dyn std::error::Error>>;
+
+    /// Returns the `limit` most recent picks.
+    fn recent_picks(&self, limit: usize) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>>;
+
+    /// Returns the picks of each of `paths`, keyed by path. Paths never picked are absent.
+    fn picks_by_path(
+        &self,
+        paths: &[&Path],
+    ) -> Result<HashMap<String, Vec<HistoryEntry>>, Box<dyn std::error::Error>>;
+
+    /// Returns the picks made from `from` up to, but not including, `to`.
+    fn picks_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>>;
+
+    /// Returns what applying `policy` at `now` would remove, without changing anything.
+    fn plan_prune(
+        &self,
+        policy: &RetentionPolicy,
+        now: DateTime<Utc>,
+    ) -> Result<PrunePlan, Box<dyn std::error::Error>>;
+
+    /// Applies `policy` at `now` and returns what was removed.
+    fn prune(
+        &mut self,
+        policy: &RetentionPolicy,
+        now: DateTime<Utc>,
+    ) -> Result<PrunePlan, Box<dyn std::error::Error>>;
+}
+
+/// The history log managed by `history_manager`, held in memory once loaded. Picks and
+/// ratings saved by other instances are merged in whenever a pick or rating is saved.
+#[derive(Debug, Clone, Default)]
+pub struct JsonHistoryStore {
+    /// The log file; `None` for the default history file.
+    path: Option<PathBuf>,
+    /// Every pick, newest first.
+    history: Vec<HistoryEntry>,
+    /// How far into the log `history` reflects.
+    position: LogPosition,
+}
+
+impl JsonHistoryStore {
+    /// Loads the history log.
+    /// If `custom_path` is provided, it uses that file instead of the default history file.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `load_history`, including `CorruptHistoryError` if the file
+    /// could not be parsed and was moved aside.
+    pub fn open(custom_path: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let (history, position) = load_history(custom_path)?;
+        Ok(Self {
+            path: custom_path.map(Path::to_path_buf),
+            history,
+            position,
+        })
+    }
+
+    /// Returns a store that starts over with an empty history, e.g. after the previous
+    /// file was found corrupt and moved aside.
+    pub fn empty(custom_path: Option<&Path>) -> Self {
+        Self {
+            path: custom_path.map(Path::to_path_buf),
+            ..Self::default()
+        }
+    }
+}
+
+impl HistoryStore for JsonHistoryStore {
+    fn load(&self) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+        Ok(self.history.clone())
+    }
+
+    fn append(&mut self, file_path: &Path) -> Result<HistoryEntry, Box<dyn std::error::Error>> {
+        add_to_history(
+            &mut self.history,
+            &mut self.position,
+            file_path,
+            self.path.as_deref(),
+        )
+    }
+
+    fn rate_latest_pick(
+        &mut self,
+        file_path: &Path,
+        rating: u8,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        rate_latest_pick(
+            &mut self.history,
+            &mut self.position,
+            file_path,
+            rating,
+            self.path.as_deref(),
+        )
+    }
+
+    fn pick_counts(&self) -> Result<HashMap<String, usize>, Box<dyn std::error::Error>> {
+        let mut counts = HashMap::new();
+        for entry in &self.history {
+            *counts.entry(entry.path.clone()).or_default() += entry.count;
+        }
+        Ok(counts)
+    }
+
+    fn recent_picks(&self, limit: usize) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+        Ok(self.history.iter().take(limit).cloned().collect())
+    }
+
+    fn picks_by_path(
+        &self,
+        paths: &[&Path],
+    ) -> Result<HashMap<String, Vec<HistoryEntry>>, Box<dyn std::error::Error>> {
+        let wanted: HashSet<String> = paths
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        let mut picks: HashMap<String, Vec<HistoryEntry>> = HashMap::new();
+        for entry in self
+            .history
+            .iter()
+            .filter(|entry| wanted.contains(&entry.path))
+        {
+            picks
+                .entry(entry.path.clone())
+                .or_default()
+                .push(entry.clone());
+        }
+        Ok(picks)
+    }
+
+    fn picks_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+        // Newest first, so the range is a contiguous slice.
+        let start = self.history.partition_point(|entry| entry.picked_at >= to);
+        let end = self
+            .history
+            .partition_point(|entry| entry.picked_at >= from);
+        Ok(self.history[start..end.max(start)].to_vec())
+    }
+
+    fn plan_prune(
+        &self,
+        policy: &RetentionPolicy,
+        now: DateTime<Utc>,
+    ) -> Result<PrunePlan, Box<dyn std::error::Error>> {
+        let expired_from = policy.expired_from(&self.history, now);
+        Ok(policy.plan(&self.history[expired_from..]))
+    }
+
+    fn prune(
+        &mut self,
+        policy: &RetentionPolicy,
+        now: DateTime<Utc>,
+    ) -> Result<PrunePlan, Box<dyn std::error::Error>> {
+        // Planned from the file rather than memory, as other instances may have added picks
+        let plan = prune_history(policy, now, self.path.as_deref())?;
+        plan.apply_to(&mut self.history);
+        if !plan.is_empty() {
+            // The log was rewritten; the next save reads it again in full.
+            self.position = LogPosition::default();
+        }
+        Ok(plan)
+    }
+}
+
+/// The pick history of a session: the store it is saved in and the retention policy
+/// applied to it. Picks and ratings go through here; queries go to the store directly, so
+/// nothing more of the history is held in memory than the store itself needs.
+pub struct SessionHistory {
+    store: Box<dyn HistoryStore>,
+    /// Applied as picks are recorded, once they have outgrown it by some slack.
+    retention: RetentionPolicy,
+    /// When the history was last pruned this session.
+    last_pruned: Option<DateTime<Utc>>,
+    /// The picks recorded since then.
+    picks_since_prune: usize,
+}
+
+impl SessionHistory {
+    /// Uses `store` for the session. Picks outside `retention` are pruned as new picks
+    /// are recorded.
+    pub fn new(store: Box<dyn HistoryStore>, retention: RetentionPolicy) -> Self {
+        Self {
+            store,
+            retention,
+            last_pruned: None,
+            picks_since_prune: 0,
+        }
+    }
+
+    /// The store, for queries about past picks.
+    pub fn store(&self) -> &dyn HistoryStore {
+        self.store.as_ref()
+    }
+
+    /// Records a pick of `file_path` made now, then prunes the history according to the
+    /// retention policy: on the first pick of the session, and after that only once the
+    /// history has outgrown the policy by `HISTORY_PRUNE_SLACK_PERCENT`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pick cannot be saved or the history cannot be pruned.
+    pub fn record_pick(&mut self, file_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        self.store.append(file_path)?;
+        self.picks_since_prune += 1;
+        let now = Utc::now();
+        if self
+            .retention
+            .prune_due(self.picks_since_prune, self.last_pruned, now)
+        {
+            self.store.prune(&self.retention, now)?;
+            self.last_pruned = Some(now);
+            self.picks_since_prune = 0;
+        }
+        Ok(())
+    }
+
+    /// Rates the most recent pick of `file_path` with 1 to 5 stars.
+    /// Returns false (and saves nothing) if the video has never been picked.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the rating cannot be saved.
+    pub fn rate_latest_pick(
+        &mut self,
+        file_path: &Path,
+        rating: u8,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        self.store.rate_latest_pick(file_path, rating)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::{cell::Cell, rc::Rc};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_json_store_queries() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("history.json");
+        let lines = [
+            r#"{"path":"/a.mp4","picked_at":"2024-01-01T00:00:00Z"}"#,
+            r#"{"path":"/b.mp4","picked_at":"2024-01-02T00:00:00Z","rating":4}"#,
+            r#"{"path":"/a.mp4","picked_at":"2024-01-03T00:00:00Z"}"#,
+        ];
+        std::fs::write(&file, lines.join("\n") + "\n").unwrap();
+        let store = JsonHistoryStore::open(Some(&file)).unwrap();
+        let day = |day| Utc.with_ymd_and_hms(2024, 1, day, 0, 0, 0).unwrap();
+
+        assert_eq!(
+            store.pick_counts().unwrap(),
+            HashMap::from([("/a.mp4".to_string(), 2), ("/b.mp4".to_string(), 1)])
+        );
+        let picks = store
+            .picks_by_path(&[Path::new("/a.mp4"), Path::new("/c.mp4")])
+            .unwrap();
+        let times: Vec<_> = picks["/a.mp4"]
+            .iter()
+            .map(|entry| entry.picked_at)
+            .collect();
+        assert_eq!(times, [day(3), day(1)]);
+        assert!(!picks.contains_key("/c.mp4"));
+
+        let between = store.picks_between(day(2), day(3)).unwrap();
+        assert_eq!(between.len(), 1);
+        assert_eq!(between[0].rating, Some(4));
+        assert!(store.picks_between(day(3), day(2)).unwrap().is_empty());
+
+        let recent = store.recent_picks(2).unwrap();
+        assert_eq!(recent[0].picked_at, day(3));
+        assert_eq!(recent[1].path, "/b.mp4");
+        assert_eq!(store.recent_picks(10).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_session_history_keeps_store_in_step() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("history.json");
+        let store = JsonHistoryStore::open(Some(&file)).unwrap();
+        let mut history = SessionHistory::new(Box::new(store), RetentionPolicy::default());
+
+        history.record_pick(Path::new("/a.mp4")).unwrap();
+        history.record_pick(Path::new("/b.mp4")).unwrap();
+        assert!(history.rate_latest_pick(Path::new("/a.mp4"), 9).unwrap());
+        assert!(!history.rate_latest_pick(Path::new("/c.mp4"), 3).unwrap());
+
+        let entries = history.store().load().unwrap();
+        assert_eq!(entries[0].path, "/b.mp4");
+        assert_eq!(entries[1].rating, Some(5));
+        assert_eq!(load_history(Some(&file)).unwrap().0, entries);
+    }
+
+    #[test]
+    fn test_session_history_prunes_on_save() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("history.json");
+        let lines = [
+            r#"{"path":"/a.mp4","picked_at":"2024-01-01T00:00:00Z"}"#,
+            r#"{"path":"/a.mp4","picked_at":"2024-01-02T00:00:00Z","rating":4}"#,
+            r#"{"path":"/b.mp4","picked_at":"2024-01-03T00:00:00Z"}"#,
+        ];
+        std::fs::write(&file, lines.join("\n") + "\n").unwrap();
+        let retention = RetentionPolicy {
+            keep_last: Some(1),
+            collapse: true,
+            ..RetentionPolicy::default()
+        };
+        let store = JsonHistoryStore::open(Some(&file)).unwrap();
+        let planned = store.plan_prune(&retention, Utc::now()).unwrap();
+        assert_eq!(planned.removed.len(), 2);
+        assert_eq!(load_history(Some(&file)).unwrap().0.len(), 3);
+
+        let mut history = SessionHistory::new(Box::new(store), retention);
+        history.record_pick(Path::new("/c.mp4")).unwrap();
+        let entries = history.store().load().unwrap();
+        let paths: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry.count))
+            .collect();
+        assert_eq!(paths, [("/c.mp4", 1), ("/b.mp4", 1), ("/a.mp4", 2)]);
+        assert_eq!(entries[2].rating, Some(4));
+        assert_eq!(load_history(Some(&file)).unwrap().0, entries);
+        assert_eq!(history.store().pick_counts().unwrap()["/a.mp4"], 2);
+    }
+
+    /// A JSON store that counts the prunes that rewrote the log.
+    struct CountingStore {
+        inner: JsonHistoryStore,
+        rewrites: Rc<Cell<usize>>,
+    }
+
+    impl HistoryStore for CountingStore {
+        fn load(&self) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+            self.inner.load()
+        }
+
+        fn append(&mut self, file_path: &Path) -> Result<HistoryEntry, Box<dyn std::error::Error>> {
+            self.inner.append(file_path)
+        }
+
+        fn rate_latest_pick(
+            &mut self,
+            file_path: &Path,
+            rating: u8,
+        ) -> Result<bool, Box<dyn std::error::Error>> {
+            self.inner.rate_latest_pick(file_path, rating)
+        }
+
+        fn pick_counts(&self) -> Result<HashMap<String, usize>, Box<dyn std::error::Error>> {
+            self.inner.pick_counts()
+        }
+
+        fn recent_picks(
+            &self,
+            limit: usize,
+        ) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+            self.inner.recent_picks(limit)
+        }
+
+        fn picks_by_path(
+            &self,
+            paths: &[&Path],
+        ) -> Result<HashMap<String, Vec<HistoryEntry>>, Box<dyn std::error::Error>> {
+            self.inner.picks_by_path(paths)
+        }
+
+        fn picks_between(
+            &self,
+            from: DateTime<Utc>,
+            to: DateTime<Utc>,
+        ) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+            self.inner.picks_between(from, to)
+        }
+
+        fn plan_prune(
+            &self,
+            policy: &RetentionPolicy,
+            now: DateTime<Utc>,
+        ) -> Result<PrunePlan, Box<dyn std::error::Error>> {
+            self.inner.plan_prune(policy, now)
+        }
+
+        fn prune(
+            &mut self,
+            policy: &RetentionPolicy,
+            now: DateTime<Utc>,
+        ) -> Result<PrunePlan, Box<dyn std::error::Error>> {
+            let plan = self.inner.prune(policy, now)?;
+            if !plan.is_empty() {
+                self.rewrites.set(self.rewrites.get() + 1);
+            }
+            Ok(plan)
+        }
+    }
+
+    #[test]
+    fn test_session_history_prunes_with_slack() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("history.json");
+        let rewrites = Rc::new(Cell::new(0));
+        let store = CountingStore {
+            inner: JsonHistoryStore::open(Some(&file)).unwrap(),
+            rewrites: Rc::clone(&rewrites),
+        };
+        let retention = RetentionPolicy {
+            keep_last: Some(100),
+            ..RetentionPolicy::default()
+        };
+        let mut history = SessionHistory::new(Box::new(store), retention);
+        for pick in 0..1000 {
+            history
+                .record_pick(Path::new(&format!("/{}.mp4", pick)))
+                .unwrap();
+        }
+
+        // Pruned once per 10 picks past the limit, not on every pick.
+        assert_eq!(rewrites.get(), 90);
+        let entries = load_history(Some(&file)).unwrap().0;
+        assert!((100..=110).contains(&entries.len()));
+        assert_eq!(entries[0].path, "/999.mp4");
+    }
+}
@@ -0,0 +1,312 @@
+// src/library_index.rs
+
+use crate::file_utils::{
+    atomic_write, collect_video_files, get_library_index_path, read_directory_listing, DirIdentity,
+    DirectoryListing, ScanOptions, ScanProgress, ScanReport,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+/// Version of the index format. Indexes written with a different version are discarded
+/// and rebuilt, since their listings may lack information newer scans depend on.
+const INDEX_VERSION: u32 = 2;
+
+/// A persistent cache of directory listings, stored next to the history file.
+///
+/// A directory's modification time changes whenever an entry is added, removed or
+/// renamed directly inside it. If the mtime still matches the stored one, the cached
+/// listing (subdirectories and files with their size and mtime) is reused instead
+/// of reading the directory again, so rescanning a large, mostly unchanged tree only
+/// costs one `stat` per directory.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LibraryIndex {
+    /// Format version; missing in indexes written before versioning was introduced.
+    #[serde(default)]
+    version: u32,
+    /// Cached listings, keyed by directory path.
+    directories: BTreeMap<PathBuf, DirectoryListing>,
+}
+
+impl Default for LibraryIndex {
+    fn default() -> Self {
+        Self {
+            version: INDEX_VERSION,
+            directories: BTreeMap::new(),
+        }
+    }
+}
+
+/// Summary of what an incremental rescan had to do.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RefreshStats {
+    /// Directories whose cached listing was still valid.
+    pub reused: usize,
+    /// Directories that had to be read from disk.
+    pub reread: usize,
+}
+
+impl LibraryIndex {
+    /// Loads the library index from disk.
+    /// If `custom_path` is provided, it uses that file instead of the default index file.
+    /// A missing or unparsable index yields an empty index, which simply means the
+    /// next refresh reads every directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index file path cannot be determined or if an
+    /// I/O error (other than `NotFound`) occurs while reading the file.
+    pub fn load(custom_path: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let index_path_buf;
+        let index_path = match custom_path {
+            Some(p) => p,
+            None => {
+                index_path_buf = get_library_index_path()?;
+                &index_path_buf
+            }
+        };
+
+        match File::open(index_path) {
+            Ok(file) => match serde_json::from_reader::<_, Self>(BufReader::new(file)) {
+                Ok(index) if index.version == INDEX_VERSION => Ok(index),
+                Ok(_) => {
+                    log::info!("Library index format changed. Rebuilding it from scratch.");
+                    Ok(Self::default())
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Could not parse library index at '{}' ({}). Rebuilding it from scratch.",
+                        index_path.display(),
+                        e
+                    );
+                    Ok(Self::default())
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// Saves the library index to disk.
+    /// If `custom_path` is provided, it saves to that file instead of the default index file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index file path cannot be determined, or if
+    /// I/O or serialization errors occur during saving. The file is replaced atomically,
+    /// so it keeps its previous contents if saving fails.
+    pub fn save(&self, custom_path: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+        let index_path_buf;
+        let index_path = match custom_path {
+            Some(p) => p,
+            None => {
+                index_path_buf = get_library_index_path()?;
+                &index_path_buf
+            }
+        };
+
+        // Compact output: the index can hold tens of thousands of entries.
+        atomic_write(index_path, |writer| {
+            serde_json::to_writer(writer, self)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Brings the index up to date for `root` and returns the scan report for it.
+    ///
+    /// Only directories whose modification time differs from the stored one are read
+    /// again. When scanning recursively, records for directories under `root` that no
+    /// longer exist (or are now excluded) are dropped. Filters and video detection are
+    /// applied on top of the cached listings, so changing them does not invalidate the index.
+    /// Listings with unreadable entries are not cached, so they are retried on every refresh.
+    /// If `progress` is given, it is updated as the refresh runs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `root` is not a directory or if a changed directory cannot be read.
+    pub fn refresh(
+        &mut self,
+        root: &Path,
+        options: &ScanOptions,
+        progress: Option<&ScanProgress>,
+    ) -> Result<(ScanReport, RefreshStats), Box<dyn std::error::Error>> {
+        let reused = AtomicUsize::new(0);
+        let reread = AtomicUsize::new(0);
+        let visited: Mutex<HashSet<PathBuf>> = Mutex::new(HashSet::new());
+        // Directories are listed on several threads; the lock is only held for lookups
+        // and updates, never while reading from disk.
+        let directories = Mutex::new(&mut self.directories);
+
+        let report = collect_video_files(root, options, progress, |dir| {
+            visited.lock().unwrap().insert(dir.to_path_buf());
+            let metadata = fs::metadata(dir)?;
+            let current_mtime = metadata.modified().ok();
+            if let Some(listing) = directories.lock().unwrap().get(dir) {
+                if listing.modified.is_some() && listing.modified == current_mtime {
+                    reused.fetch_add(1, Ordering::Relaxed);
+                    let mut listing = listing.clone();
+                    listing.identity = DirIdentity::of(dir, &metadata);
+                    return Ok(listing);
+                }
+            }
+            let listing = read_directory_listing(dir)?;
+            reread.fetch_add(1, Ordering::Relaxed);
+            let mut directories = directories.lock().unwrap();
+            if listing.skipped.is_empty() {
+                let mut cached = listing.clone();
+                cached.identity = None;
+                directories.insert(dir.to_path_buf(), cached);
+            } else {
+                directories.remove(dir);
+            }
+            Ok(listing)
+        })?;
+        let stats = RefreshStats {
+            reused: reused.into_inner(),
+            reread: reread.into_inner(),
+        };
+        let visited = visited.into_inner().unwrap();
+
+        if options.recursive {
+            // Everything reachable under `root` was visited, so unvisited records are stale.
+            self.directories
+                .retain(|dir, _| !dir.starts_with(root) || visited.contains(dir));
+        }
+
+        log::debug!(
+            "Library index refresh for '{}': {} directories reused, {} re-read.",
+            root.display(),
+            stats.reused,
+            stats.reread
+        );
+        Ok((report, stats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_utils::FileRecord;
+    use tempfile::tempdir;
+
+    fn recursive() -> ScanOptions {
+        ScanOptions {
+            recursive: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_refresh_matches_full_scan() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        File::create(root.join("video1.mp4")).unwrap();
+        File::create(root.join("notes.txt")).unwrap();
+        fs::create_dir(root.join("sub")).unwrap();
+        File::create(root.join("sub").join("video2.mkv")).unwrap();
+
+        let mut index = LibraryIndex::default();
+        let (report, stats) = index.refresh(root, &recursive(), None).unwrap();
+
+        assert_eq!(
+            report.videos,
+            crate::file_utils::find_video_files(root, &recursive()).unwrap()
+        );
+        assert_eq!(stats.reread, 2);
+        assert_eq!(stats.reused, 0);
+    }
+
+    #[test]
+    fn test_refresh_reuses_unchanged_directories() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        File::create(root.join("video1.mp4")).unwrap();
+
+        let mut index = LibraryIndex::default();
+        index.refresh(root, &recursive(), None).unwrap();
+
+        // Tamper with the cached listing: if the directory is not re-read,
+        // the fake entry must come back from the refresh.
+        let fake = root.join("only_in_index.mp4");
+        index
+            .directories
+            .get_mut(root)
+            .unwrap()
+            .files
+            .push(FileRecord {
+                path: fake.clone(),
+                size: 0,
+                modified: None,
+            });
+
+        let (report, stats) = index.refresh(root, &recursive(), None).unwrap();
+        assert!(report.videos.contains(&fake));
+        assert_eq!(stats.reread, 0);
+        assert_eq!(stats.reused, 1);
+    }
+
+    #[test]
+    fn test_refresh_picks_up_changes_and_prunes_removed_dirs() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let sub = root.join("sub");
+        fs::create_dir(&sub).unwrap();
+        File::create(sub.join("old.mp4")).unwrap();
+
+        let mut index = LibraryIndex::default();
+        index.refresh(root, &recursive(), None).unwrap();
+        assert!(index.directories.contains_key(&sub));
+
+        fs::remove_dir_all(&sub).unwrap();
+        File::create(root.join("new.mkv")).unwrap();
+
+        let (report, _) = index.refresh(root, &recursive(), None).unwrap();
+        assert_eq!(report.videos, vec![root.join("new.mkv")]);
+        assert!(!index.directories.contains_key(&sub));
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("videos");
+        fs::create_dir(&root).unwrap();
+        File::create(root.join("video1.mp4")).unwrap();
+        let index_path = dir.path().join("library_index.json");
+
+        let mut index = LibraryIndex::default();
+        index.refresh(&root, &recursive(), None).unwrap();
+        index.save(Some(&index_path)).unwrap();
+
+        let mut loaded = LibraryIndex::load(Some(&index_path)).unwrap();
+        assert_eq!(loaded.directories, index.directories);
+        let (report, stats) = loaded.refresh(&root, &recursive(), None).unwrap();
+        assert_eq!(report.videos, vec![root.join("video1.mp4")]);
+        assert_eq!(stats.reread, 0);
+    }
+
+    #[test]
+    fn test_load_missing_or_corrupt_index() {
+        let dir = tempdir().unwrap();
+        let missing = dir.path().join("missing.json");
+        assert!(LibraryIndex::load(Some(&missing))
+            .unwrap()
+            .directories
+            .is_empty());
+
+        let corrupt = dir.path().join("corrupt.json");
+        fs::write(&corrupt, "{ not json").unwrap();
+        assert!(LibraryIndex::load(Some(&corrupt))
+            .unwrap()
+            .directories
+            .is_empty());
+    }
+}
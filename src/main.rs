@@ -1,36 +1,75 @@
 // src/main.rs
 
 use actix_web::{dev::ServerHandle, web};
+use chrono::{DateTime, Local, Utc};
 use clap::Parser;
-use dialoguer::{theme::ColorfulTheme, Input, Select};
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
 use local_ip_address::local_ip;
 use qrcode::render::unicode;
 use qrcode::QrCode;
-use rand::prelude::*;
-use std::collections::HashMap;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fmt;
 use std::path::{Path, PathBuf};
 use std::process;
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
 // Module declarations (ensure these match your project structure)
+mod candidate_filter;
 mod cli;
 mod config;
+mod duplicate_finder;
 mod file_utils;
+mod folder_balance;
+mod folder_watcher;
 mod history_manager;
+mod history_store;
+mod library_index;
 mod metadata_retriever;
+mod playlist;
+mod preferences;
+mod repeat_window;
+mod retention;
+mod scan_filter;
+mod selection_strategy;
+mod series;
+mod settings;
+mod shuffle_bag;
+mod sqlite_history;
 mod stream_server;
 mod ui;
 mod video_entry;
+mod video_sniffer;
 
 // Crate imports
-use crate::cli::Cli;
-use crate::file_utils::find_video_files;
-use crate::history_manager::{add_to_history, load_history, HistoryEntry};
+use crate::candidate_filter::{CandidateCache, CandidateFilter, DurationCache};
+use crate::cli::{Cli, Command, HistoryCommand};
+use crate::config::PLAYLIST_TEMP_FILE_NAME;
+use crate::duplicate_finder::{DuplicateCache, DuplicateIndex, HashCache, HashMode};
+use crate::file_utils::{
+    dedup_roots, get_history_database_path, get_settings_path, root_of, scan_video_files,
+    ScanOptions, ScanReport,
+};
+use crate::folder_watcher::{FolderWatcher, SharedVideoList};
+use crate::history_manager::CorruptHistoryError;
+use crate::history_store::{HistoryBackend, HistoryStore, JsonHistoryStore, SessionHistory};
+use crate::library_index::LibraryIndex;
 use crate::metadata_retriever::get_video_metadata;
+use crate::playlist::{Playlist, PlaylistTarget};
+use crate::preferences::VideoPreferences;
+use crate::repeat_window::RepeatWindow;
+use crate::scan_filter::ScanFilter;
+use crate::selection_strategy::{SelectionStrategy, StrategyKind, StrategyOptions};
+use crate::series::parse_episode;
+use crate::settings::Settings;
+use crate::sqlite_history::SqliteHistoryStore;
 use crate::stream_server::{run_server, StreamState}; // Assuming StreamState is pub
-use crate::ui::view_history;
+use crate::ui::{
+    choose_strategy, edit_candidate_filter, format_rating, prompt_rating, show_duplicate_groups,
+    show_playlist, show_prune_plan, show_scan_summary, view_history, with_scan_progress,
+};
 use crate::video_entry::VideoEntry;
 
 const STREAMING_PORT: u16 = 8080;
@@ -55,6 +94,17 @@ fn play_video_locally(video_path: &Path) -> Result<(), Box<dyn std::error::Error
     }
 }
 
+/// Prints the streaming URL for a video, with a QR code to open it on another device.
+fn show_streaming_url(video_path: &Path, url: &str) {
+    println!("Streaming URL for '{}': {}", video_path.display(), url);
+    if let Ok(code) = QrCode::new(url.as_bytes()) {
+        println!(
+            "Scan QR code to stream on another device:\n{}",
+            code.render::<unicode::Dense1x2>().build()
+        );
+    }
+}
+
 /// Enum to control the flow of the main loop when no videos are found.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum LoopControl {
@@ -71,6 +121,23 @@ enum PostActionOutcome {
     QuitApplication,
 }
 
+/// The selection settings the action menu can change. Changes apply from the next pick.
+struct MenuSettings<'a> {
+    candidate_filter: &'a mut CandidateFilter,
+    strategy_kind: &'a mut StrategyKind,
+    preferences: &'a mut VideoPreferences,
+}
+
+/// The result of the most recent scan, reused by "Pick another from this folder".
+/// `videos` is the merged list for all scanned folders. While watchers are attached,
+/// it is kept in sync with files appearing and disappearing under any of them.
+struct CachedScan {
+    folders: Vec<PathBuf>,
+    videos: SharedVideoList,
+    // Dropping the cache (e.g. when changing folders) also stops watching.
+    watchers: Vec<FolderWatcher>,
+}
+
 // Custom application error type
 #[derive(Debug)]
 enum AppError {
@@ -97,28 +164,142 @@ async fn main() {
     }
 }
 
-/// Initializes application state: logger, CLI args, theme, and history.
-fn initialize_app_state(
-) -> Result<(Cli, ColorfulTheme, Vec<HistoryEntry>), Box<dyn std::error::Error>> {
+/// Initializes application state: logger, CLI args, and theme.
+fn initialize_app_state() -> (Cli, ColorfulTheme) {
     dotenvy::dotenv().ok(); // Load .env file if present
     env_logger::init(); // Initialize logger
     let cli_args = Cli::parse(); // Parse command line arguments
     let theme = ColorfulTheme::default(); // Set default theme for dialoguer
-    let history = load_history(None)?; // Load historical data
-    Ok((cli_args, theme, history))
+    (cli_args, theme)
+}
+
+/// Opens the history store selected by the settings and loads the pick history from it.
+fn open_history(
+    settings: &Settings,
+    theme: &ColorfulTheme,
+) -> Result<SessionHistory, Box<dyn std::error::Error>> {
+    let store: Box<dyn HistoryStore> = match settings.history_backend {
+        HistoryBackend::Json => Box::new(load_history_or_recover(theme)?),
+        HistoryBackend::Sqlite => {
+            Box::new(SqliteHistoryStore::open(&get_history_database_path()?)?)
+        }
+    };
+    Ok(SessionHistory::new(store, settings.history_retention))
+}
+
+/// Loads the JSON history. If the history file is corrupt, `load_history` has already
+/// moved it aside; the user is told where it went and asked whether to start over with
+/// an empty history or stop (e.g. to repair the file by hand).
+fn load_history_or_recover(
+    theme: &ColorfulTheme,
+) -> Result<JsonHistoryStore, Box<dyn std::error::Error>> {
+    let error = match JsonHistoryStore::open(None) {
+        Ok(store) => return Ok(store),
+        Err(error) => error,
+    };
+    let Some(corrupt) = error.downcast_ref::<CorruptHistoryError>() else {
+        return Err(error);
+    };
+    eprintln!("\n⚠️  Warning: {}.", corrupt);
+    eprintln!("Your previous picks are kept in that file; nothing has been deleted.");
+    let start_over = Confirm::with_theme(theme)
+        .with_prompt("Continue with an empty history?")
+        .default(true)
+        .interact()?;
+    if start_over {
+        Ok(JsonHistoryStore::empty(None))
+    } else {
+        Err(error)
+    }
 }
 
-/// Determines the initial folder path from CLI arguments or environment variables.
-fn determine_initial_folder_path(cli_args: &Cli) -> Option<PathBuf> {
-    cli_args
-        .folder
+/// Loads the on-disk library index, or returns `None` if it is disabled via `--no-index`.
+fn load_library_index(
+    no_index_flag: bool,
+) -> Result<Option<LibraryIndex>, Box<dyn std::error::Error>> {
+    if no_index_flag {
+        return Ok(None);
+    }
+    Ok(Some(LibraryIndex::load(None)?))
+}
+
+/// The separator between folders in `DEFAULT_VIDEO_FOLDER` and the folder prompt,
+/// matching the one used in `PATH`.
+const FOLDER_LIST_SEPARATOR: char = if cfg!(windows) { ';' } else { ':' };
+
+/// Determines the initial folders from CLI arguments or environment variables.
+/// `DEFAULT_VIDEO_FOLDER` may list several folders, separated as in `PATH`.
+fn determine_initial_folders(cli_args: &Cli) -> Option<Vec<PathBuf>> {
+    let folders: Vec<PathBuf> = if cli_args.folder.is_empty() {
+        // Fallback to environment variable
+        let list = env::var("DEFAULT_VIDEO_FOLDER").ok()?;
+        env::split_paths(&list)
+            .filter(|p| !p.as_os_str().is_empty())
+            .map(|p| PathBuf::from(shellexpand::tilde(&p.to_string_lossy()).into_owned()))
+            .collect()
+    } else {
+        cli_args
+            .folder
+            .iter()
+            .map(|s| PathBuf::from(shellexpand::tilde(s).into_owned())) // Expand tilde for home dir
+            .collect()
+    };
+    let folders = dedup_roots(folders);
+    (!folders.is_empty()).then_some(folders)
+}
+
+/// Formats a list of folders for display, e.g. `'/mnt/a', '/mnt/b'`.
+fn describe_folders(folders: &[PathBuf]) -> String {
+    folders
+        .iter()
+        .map(|folder| format!("'{}'", folder.display()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Builds the scan settings from the CLI arguments, falling back to the config file.
+fn build_scan_options(
+    cli_args: &Cli,
+    settings: &Settings,
+) -> Result<ScanOptions, Box<dyn std::error::Error>> {
+    let mut options = ScanOptions {
+        recursive: !cli_args.non_recursive,
+        filter: ScanFilter::new(&cli_args.include, &cli_args.exclude)?,
+        detect_by_content: cli_args.detect_by_content || settings.detect_by_content,
+        skip_unreadable: !cli_args.strict_scan,
+        follow_symlinks: cli_args.follow_symlinks,
+        include_hidden: cli_args.include_hidden,
+        same_filesystem: cli_args.same_filesystem,
+        max_depth: cli_args.max_depth.map(|depth| depth as usize),
+        ..Default::default()
+    };
+    if let Some(extensions) = cli_args
+        .extensions
         .as_ref()
-        .map(|s| PathBuf::from(shellexpand::tilde(s).into_owned())) // Expand tilde for home dir
-        .or_else(|| {
-            env::var("DEFAULT_VIDEO_FOLDER") // Fallback to environment variable
-                .ok()
-                .map(|s| PathBuf::from(shellexpand::tilde(&s).into_owned()))
-        })
+        .or(settings.video_extensions.as_ref())
+    {
+        options.extensions = ScanOptions::normalize_extensions(extensions);
+    }
+    if let Some(threads) = cli_args.scan_threads {
+        options.threads = threads as usize;
+    }
+    if options.extensions.is_empty() && !options.detect_by_content {
+        log::warn!(
+            "No video extensions configured and content detection is off; no files will be found."
+        );
+    }
+    Ok(options)
+}
+
+/// Builds the initial candidate filters from the CLI arguments.
+fn build_candidate_filter(cli_args: &Cli) -> CandidateFilter {
+    CandidateFilter {
+        min_size: cli_args.min_size,
+        max_size: cli_args.max_size,
+        modified_within: cli_args.modified_within,
+        min_duration: cli_args.min_duration,
+        max_duration: cli_args.max_duration,
+    }
 }
 
 /// Sets up and starts the Actix web server for streaming if not disabled.
@@ -185,107 +366,202 @@ fn destructure_streaming_components(
     }
 }
 
-/// Prompts the user for a folder path if one is not already set.
-fn get_or_prompt_folder_path(
-    current_folder_path_opt: &Option<PathBuf>, // Note: changed to &Option
+/// Prompts the user for one or more folder paths if none are already set.
+fn get_or_prompt_folders(
+    current_folders_opt: &Option<Vec<PathBuf>>, // Note: changed to &Option
     theme: &ColorfulTheme,
-    cached_folder_scan: &mut Option<(PathBuf, Vec<PathBuf>)>, // Mutable to clear cache if prompting
-) -> Result<PathBuf, Box<dyn std::error::Error>> {
-    if let Some(path) = current_folder_path_opt {
-        return Ok(path.clone());
+    cached_folder_scan: &mut Option<CachedScan>, // Mutable to clear cache if prompting
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    if let Some(folders) = current_folders_opt {
+        return Ok(folders.clone());
     }
 
-    // If no current path, prompt user and invalidate cache
+    // If no current folders, prompt user and invalidate cache
     *cached_folder_scan = None;
     let input = Input::<String>::with_theme(theme)
-        .with_prompt("Enter the path to the video folder (supports ~ and env vars)")
+        .with_prompt(format!(
+            "Enter the path to the video folder (supports ~ and env vars; separate several folders with '{}')",
+            FOLDER_LIST_SEPARATOR
+        ))
         .interact_text()?; // Propagates error if user cancels (e.g., Ctrl+C)
-    Ok(PathBuf::from(shellexpand::full(&input)?.into_owned())) // Expand env vars and tilde
+    let mut folders = Vec::new();
+    for entry in input.split(FOLDER_LIST_SEPARATOR) {
+        let entry = entry.trim();
+        if !entry.is_empty() {
+            // Expand env vars and tilde
+            folders.push(PathBuf::from(shellexpand::full(entry)?.into_owned()));
+        }
+    }
+    Ok(dedup_roots(folders))
 }
 
-/// Validates if the given path is an accessible directory.
-/// Updates `current_folder_path_opt` and `cached_folder_scan` to None on validation failure to force re-prompt.
-fn validate_folder_path(
-    folder_to_scan: &Path,
-    current_folder_path_opt: &mut Option<PathBuf>,
-    cached_folder_scan: &mut Option<(PathBuf, Vec<PathBuf>)>,
-) -> bool {
-    if !folder_to_scan.is_dir() {
-        eprintln!(
-            "The path '{}' is not a valid directory.",
-            folder_to_scan.display()
-        );
-        *current_folder_path_opt = None; // Force re-prompt
-        *cached_folder_scan = None;
+/// Checks whether the given path is an accessible directory, reporting why if it is not.
+fn is_accessible_folder(folder: &Path) -> bool {
+    if !folder.is_dir() {
+        eprintln!("The path '{}' is not a valid directory.", folder.display());
         return false;
     }
     // Check read permissions
-    if let Err(e) = std::fs::read_dir(folder_to_scan) {
+    if let Err(e) = std::fs::read_dir(folder) {
         eprintln!(
             "Error: Cannot access directory '{}'. Please check permissions. (Details: {})",
-            folder_to_scan.display(),
-            e
-        );
-        log::error!(
-            "Failed to read directory '{}': {}",
-            folder_to_scan.display(),
+            folder.display(),
             e
         );
-        *current_folder_path_opt = None; // Force re-prompt
-        *cached_folder_scan = None;
+        log::error!("Failed to read directory '{}': {}", folder.display(), e);
         return false;
     }
     true // Path is a valid, accessible directory
 }
 
-/// Scans the folder for video files, utilizing a cache.
-/// Updates `current_folder_path_opt` and `cached_folder_scan` on error to force re-prompt.
+/// Returns the folders that are accessible directories. Inaccessible ones (e.g. an
+/// unplugged drive) are skipped with a message, so the rest of the library stays usable.
+/// Updates `current_folders_opt` and `cached_folder_scan` to None if none are usable, to force re-prompt.
+fn validate_folders(
+    folders: &[PathBuf],
+    current_folders_opt: &mut Option<Vec<PathBuf>>,
+    cached_folder_scan: &mut Option<CachedScan>,
+) -> Vec<PathBuf> {
+    let usable: Vec<PathBuf> = folders
+        .iter()
+        .filter(|folder| is_accessible_folder(folder))
+        .cloned()
+        .collect();
+    if usable.is_empty() {
+        *current_folders_opt = None; // Force re-prompt
+        *cached_folder_scan = None;
+    } else if usable.len() < folders.len() {
+        println!(
+            "Continuing with the {} accessible folder(s) out of {}.",
+            usable.len(),
+            folders.len()
+        );
+    }
+    usable
+}
+
+/// Scans the folders for video files, utilizing a cache, and merges the results into
+/// one deduplicated list.
+/// When a library index is available, each scan is an incremental refresh that only
+/// re-reads directories changed since the last run; the index is saved afterwards.
+/// If `watch_folder` is set, filesystem watchers keep the cached list current afterwards.
+/// A folder that fails to scan is reported and left out; if all of them fail,
+/// `current_folders_opt` and `cached_folder_scan` are reset to force re-prompt.
 fn scan_for_videos(
-    folder_to_scan: &Path,
-    scan_recursively: bool,
-    cached_folder_scan: &mut Option<(PathBuf, Vec<PathBuf>)>,
-    current_folder_path_opt: &mut Option<PathBuf>, // To reset on error
+    folders_to_scan: &[PathBuf],
+    scan_options: &ScanOptions,
+    cached_folder_scan: &mut Option<CachedScan>,
+    current_folders_opt: &mut Option<Vec<PathBuf>>, // To reset on error
+    library_index: &mut Option<LibraryIndex>,
+    watch_folder: bool,
 ) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
     // Check cache first
-    if let Some((cached_path, files)) = cached_folder_scan {
-        if cached_path == folder_to_scan {
-            println!("Using cached file list for '{}'.", cached_path.display());
-            return Ok(files.clone());
+    if let Some(cached) = cached_folder_scan {
+        if cached.folders == folders_to_scan {
+            let live_note = if cached.watchers.is_empty() {
+                ""
+            } else {
+                " (kept up to date by the folder watcher)"
+            };
+            println!(
+                "Using cached file list for {}{}.",
+                describe_folders(&cached.folders),
+                live_note
+            );
+            return Ok(cached.videos.lock().unwrap().clone());
         }
     }
 
-    // If not in cache or different folder, scan
-    println!("Scanning folder '{}'...", folder_to_scan.display());
-    match find_video_files(folder_to_scan, scan_recursively) {
-        Ok(files) => {
-            *cached_folder_scan = Some((folder_to_scan.to_path_buf(), files.clone())); // Update cache
-            Ok(files)
+    // If not in cache or different folders, scan each of them
+    let mut scanned_folders = Vec::new();
+    let mut reports = Vec::new();
+    let mut last_error = None;
+    for folder in folders_to_scan {
+        println!("Scanning folder '{}'...", folder.display());
+        let scan_result = with_scan_progress(|progress| match library_index {
+            Some(index) => index
+                .refresh(folder, scan_options, Some(progress))
+                .map(|(report, stats)| (report, Some(stats))),
+            None => {
+                scan_video_files(folder, scan_options, Some(progress)).map(|report| (report, None))
+            }
+        })
+        .map(|(report, stats)| {
+            if let Some(stats) = stats {
+                println!(
+                    "Library index: {} directories unchanged, {} re-read.",
+                    stats.reused, stats.reread
+                );
+            }
+            report
+        });
+        match scan_result {
+            Ok(report) => {
+                scanned_folders.push(folder.clone());
+                reports.push(report);
+            }
+            Err(e) => {
+                eprintln!("Error scanning folder '{}': {}", folder.display(), e);
+                last_error = Some(e);
+            }
         }
-        Err(e) => {
-            eprintln!(
-                "Error scanning folder '{}': {}",
-                folder_to_scan.display(),
-                e
-            );
-            *current_folder_path_opt = None; // Reset to re-prompt
-            *cached_folder_scan = None; // Clear cache on error
-            Err(e) // Propagate scan error
+    }
+    if let Some(index) = library_index {
+        if let Err(e) = index.save(None) {
+            log::warn!("Failed to save library index: {}", e);
         }
     }
+
+    if scanned_folders.is_empty() {
+        *current_folders_opt = None; // Reset to re-prompt
+        *cached_folder_scan = None; // Clear cache on error
+        return Err(last_error.unwrap_or_else(|| "No folders to scan.".into())); // Propagate scan error
+    }
+
+    let report = ScanReport::merge(reports);
+    show_scan_summary(&report);
+    if scanned_folders.len() > 1 {
+        println!(
+            "Combined library: {} videos from {} folders.",
+            report.videos.len(),
+            scanned_folders.len()
+        );
+    }
+    let files = report.videos;
+    let videos: SharedVideoList = Arc::new(Mutex::new(files.clone()));
+    let mut watchers = Vec::new();
+    if watch_folder {
+        for folder in &scanned_folders {
+            match FolderWatcher::start(folder, scan_options, videos.clone()) {
+                Ok(watcher) => watchers.push(watcher),
+                Err(e) => log::warn!(
+                    "Could not watch '{}' for changes: {}. Use 'Rescan current folder' to pick up new files.",
+                    folder.display(),
+                    e
+                ),
+            }
+        }
+    }
+    *cached_folder_scan = Some(CachedScan {
+        folders: folders_to_scan.to_vec(),
+        videos,
+        watchers,
+    }); // Update cache
+    Ok(files)
 }
 
 /// Handles user interaction when no video files are found in a directory.
 fn handle_no_videos_found_action_logic(
     folder_path_display: &str,
     theme: &ColorfulTheme,
-    history: &[HistoryEntry],
-    current_folder_path: &mut Option<PathBuf>,
-    cached_folder_scan: &mut Option<(PathBuf, Vec<PathBuf>)>,
+    history: &dyn HistoryStore,
+    current_folders: &mut Option<Vec<PathBuf>>,
+    cached_folder_scan: &mut Option<CachedScan>,
 ) -> Result<LoopControl, Box<dyn std::error::Error>> {
-    println!("No video files found in '{}'.", folder_path_display);
+    println!("No video files found in {}.", folder_path_display);
     let action = Select::with_theme(theme)
         .with_prompt("No videos found. What would you like to do?")
-        .items(&["Choose another folder", "View history", "Quit"])
+        .items(["Choose another folder", "View history", "Quit"])
         .default(0)
         .interact_opt()? // Returns Option<usize>, None if Esc
         .unwrap_or(2); // Default to Quit (index 2) if Esc is pressed
@@ -293,13 +569,13 @@ fn handle_no_videos_found_action_logic(
     match action {
         0 => {
             // Choose another folder
-            *current_folder_path = None;
+            *current_folders = None;
             *cached_folder_scan = None;
             Ok(LoopControl::Continue)
         }
         1 => {
             // View history
-            view_history(history, theme)?;
+            view_history(&history.load()?, theme)?;
             Ok(LoopControl::Continue) // Continue outer loop, re-evaluating current folder
         }
         _ => {
@@ -309,30 +585,98 @@ fn handle_no_videos_found_action_logic(
     }
 }
 
-/// Selects a video from the list using weighted random choice based on pick history.
+/// Handles user interaction when videos were found, but none of them pass the candidate filters.
+fn handle_no_matching_videos_logic(
+    theme: &ColorfulTheme,
+    candidate_filter: &mut CandidateFilter,
+    current_folders: &mut Option<Vec<PathBuf>>,
+    cached_folder_scan: &mut Option<CachedScan>,
+) -> Result<LoopControl, Box<dyn std::error::Error>> {
+    println!("No videos match the active filters ({}).", candidate_filter);
+    let action = Select::with_theme(theme)
+        .with_prompt("No videos match. What would you like to do?")
+        .items(["Edit filters", "Clear filters", "Choose another folder", "Quit"])
+        .default(0)
+        .interact_opt()?
+        .unwrap_or(3); // Default to Quit (index 3) if Esc is pressed
+
+    match action {
+        0 => {
+            edit_candidate_filter(candidate_filter, theme)?;
+            Ok(LoopControl::Continue)
+        }
+        1 => {
+            *candidate_filter = CandidateFilter::default();
+            Ok(LoopControl::Continue)
+        }
+        2 => {
+            *current_folders = None;
+            *cached_folder_scan = None;
+            Ok(LoopControl::Continue)
+        }
+        _ => Ok(LoopControl::Break),
+    }
+}
+
+/// Selects a video from the list with the given strategy, based on pick history.
+/// Copies listed in `duplicates` count as one video: only the first copy in the list is
+/// a candidate, and picks of any copy count towards its weight.
+/// Videos inside `repeat_window` are excluded before the strategy runs, and favorites in
+/// `preferences` get their weight multiplier.
+/// All randomness comes from `rng`, so the same seed, files and history give the same pick.
 fn select_video_logic(
     video_files_paths: &[PathBuf],
-    history: &[HistoryEntry],
+    history: &SessionHistory,
+    duplicates: &DuplicateIndex,
+    repeat_window: &RepeatWindow,
+    preferences: &VideoPreferences,
+    strategy: &mut dyn SelectionStrategy,
+    rng: &mut dyn Rng,
 ) -> Result<VideoEntry, Box<dyn std::error::Error>> {
-    // Calculate pick counts from history
-    let history_pick_counts: HashMap<String, usize> = {
-        let mut counts = HashMap::new();
-        for entry in history {
-            *counts.entry(entry.path.clone()).or_insert(0) += 1;
+    // One time for the whole pick, so the repeat window and the strategy agree
+    let now = Utc::now();
+
+    // Look up the picks of the candidates and their copies only, rather than the whole history
+    let mut lookup: Vec<&Path> = video_files_paths.iter().map(PathBuf::as_path).collect();
+    for path in video_files_paths {
+        lookup.extend(duplicates.other_copies(duplicates.canonical(path)).map(PathBuf::as_path));
+    }
+    lookup.sort_unstable();
+    lookup.dedup();
+    let picks = history.store().picks_by_path(&lookup)?;
+
+    // Collect pick times and the latest rating per group of identical copies
+    let mut history_pick_times: HashMap<PathBuf, Vec<DateTime<Utc>>> = HashMap::new();
+    let mut latest_rated: HashMap<PathBuf, (DateTime<Utc>, u8)> = HashMap::new();
+    for entry in picks.values().flatten() {
+        let canonical = duplicates.canonical(Path::new(&entry.path)).to_path_buf();
+        if let Some(rating) = entry.rating {
+            let latest = latest_rated.entry(canonical.clone()).or_insert((entry.picked_at, rating));
+            if entry.picked_at > latest.0 {
+                *latest = (entry.picked_at, rating);
+            }
         }
-        counts
-    };
+        // A collapsed entry counts as all the picks it stands for
+        let times = std::iter::repeat_n(entry.picked_at, entry.count);
+        history_pick_times.entry(canonical).or_default().extend(times);
+    }
 
-    // Create VideoEntry objects with pick counts
+    // Create VideoEntry objects with pick counts and times, one per logical video
+    let mut seen_groups = HashSet::new();
     let video_entries: Vec<VideoEntry> = video_files_paths
         .iter()
+        .filter(|path_ref| seen_groups.insert(duplicates.canonical(path_ref)))
         .map(|path_ref| {
-            let path_str = path_ref.to_string_lossy();
-            let pick_count = history_pick_counts
-                .get(path_str.as_ref())
-                .copied()
-                .unwrap_or(0);
-            VideoEntry::new(path_ref.clone(), pick_count)
+            let picked_at = history_pick_times
+                .get(duplicates.canonical(path_ref))
+                .cloned()
+                .unwrap_or_default();
+            let mut entry = VideoEntry::with_picks(path_ref.clone(), picked_at);
+            entry.weight_multiplier = preferences.weight_multiplier(duplicates.copies(path_ref));
+            entry.rating = latest_rated
+                .get(duplicates.canonical(path_ref))
+                .map(|&(_, rating)| rating);
+            entry
         })
         .collect();
 
@@ -355,27 +699,74 @@ fn select_video_logic(
         ))); // Return our custom error, boxed
     }
 
-    // Perform weighted random selection
-    video_entries
-        .choose_weighted(&mut rand::rng(), |item| item.weight())
-        .cloned()
-        .map_err(|e| {
-            log::error!(
-                "Error during weighted choice: {:?}. Video entries count: {}",
-                e,
-                video_entries.len()
+    // Exclude recently picked videos, including every copy of them
+    let video_entries = if repeat_window.is_empty() {
+        video_entries
+    } else {
+        let mut recent = HashSet::new();
+        for path in repeat_window.recent_paths(history.store(), now)? {
+            let canonical = duplicates.canonical(Path::new(&path));
+            recent.insert(canonical.to_path_buf());
+            recent.extend(duplicates.other_copies(canonical).cloned());
+        }
+        let windowed = repeat_window.apply(video_entries, &recent);
+        if windowed.relaxed {
+            println!(
+                "Every candidate was picked within the no-repeat window ({}); relaxing it for this pick.",
+                repeat_window
             );
-            Box::new(e) as Box<dyn std::error::Error> // Convert WeightedError
-        })
+        } else if windowed.excluded > 0 {
+            log::info!(
+                "No-repeat window ({}) excluded {} videos.",
+                repeat_window,
+                windowed.excluded
+            );
+        }
+        windowed.entries
+    };
+
+    // Let the strategy choose
+    strategy
+        .select(&video_entries, now, rng)
+        .cloned()
+        .ok_or_else(|| AppError::NoVideoEntriesAvailable.into())
 }
 
 /// Displays information about the selected video (path, pick count, metadata).
-fn display_selected_video_info(selected_video_entry: &VideoEntry) {
+/// When several folders are combined, also shows which one the video came from,
+/// and lists any identical copies found by duplicate detection.
+fn display_selected_video_info(
+    selected_video_entry: &VideoEntry,
+    folders: &[PathBuf],
+    duplicates: &DuplicateIndex,
+) {
+    let last_picked_note = selected_video_entry
+        .last_picked
+        .map(|time| {
+            let local_time: DateTime<Local> = DateTime::from(time);
+            format!(", last on {}", local_time.format("%Y-%m-%d"))
+        })
+        .unwrap_or_default();
     println!(
-        "\n✨ Picked: {} (Previously picked {} times)",
+        "\n✨ Picked: {} (Previously picked {} times{})",
         selected_video_entry.path.display(),
-        selected_video_entry.pick_count
+        selected_video_entry.pick_count,
+        last_picked_note
     );
+    if let Some(rating) = selected_video_entry.rating {
+        println!("Rating: {}", format_rating(rating));
+    }
+    if let Some(episode) = parse_episode(&selected_video_entry.path) {
+        println!("Episode: {}", episode);
+    }
+    for copy in duplicates.other_copies(&selected_video_entry.path) {
+        println!("Identical copy: {}", copy.display());
+    }
+    if folders.len() > 1 {
+        if let Some(root) = root_of(&selected_video_entry.path, folders) {
+            println!("From folder: {}", root.display());
+        }
+    }
     // Attempt to get and display video metadata
     if let Ok(metadata) = get_video_metadata(&selected_video_entry.path) {
         println!(
@@ -391,12 +782,18 @@ fn display_selected_video_info(selected_video_entry: &VideoEntry) {
 /// Handles the inner loop of user actions for a selected video.
 async fn loop_user_actions(
     selected_video_entry: &VideoEntry,
-    history: &[HistoryEntry], // Pass as slice, history is updated before this call
+    history: &mut SessionHistory, // Updated before this call; ratings update it
     theme: &ColorfulTheme,
+    settings: MenuSettings<'_>,
     stream_state_arc: &Option<StreamState>,
     stream_url_base: &Option<String>,
 ) -> Result<PostActionOutcome, Box<dyn std::error::Error>> {
     let selected_file = &selected_video_entry.path;
+    let MenuSettings {
+        candidate_filter,
+        strategy_kind,
+        preferences,
+    } = settings;
 
     loop {
         let mut actions = vec!["Play locally"];
@@ -424,9 +821,18 @@ async fn loop_user_actions(
             }
         }
 
+        actions.push(if preferences.is_favorite(selected_file) {
+            "Remove from favorites"
+        } else {
+            "Favorite"
+        });
         actions.extend(vec![
+            "Rate this video",
+            "Never show again",
             "Pick another from this folder",
             "Rescan current folder",
+            "Filter candidates",
+            "Change selection strategy",
             "Choose a different folder",
             "View history",
             "Quit",
@@ -474,18 +880,7 @@ async fn loop_user_actions(
                         *guard = Some(selected_file.clone());
                     }
                     let full_stream_url = format!("{}/stream", base_url_ref_display);
-                    println!(
-                        "Streaming URL for '{}': {}",
-                        selected_file.display(),
-                        full_stream_url
-                    );
-                    // Display QR code for the streaming URL
-                    if let Ok(code) = QrCode::new(full_stream_url.as_bytes()) {
-                        println!(
-                            "Scan QR code to stream on another device:\n{}",
-                            code.render::<unicode::Dense1x2>().build()
-                        );
-                    }
+                    show_streaming_url(selected_file, &full_stream_url);
                 } else {
                     println!("Streaming is not available or was not enabled for this session.");
                 }
@@ -493,28 +888,62 @@ async fn loop_user_actions(
             }
             Some("Get Streaming Link (current video)") => {
                 if let Some(url) = &current_video_streaming_url {
-                    println!("Streaming URL for '{}': {}", selected_file.display(), url);
-                    if let Ok(code) = QrCode::new(url.as_bytes()) {
-                        println!(
-                            "Scan QR code to stream on another device:\n{}",
-                            code.render::<unicode::Dense1x2>().build()
-                        );
-                    }
+                    show_streaming_url(selected_file, url);
                 } else {
                     // This case should ideally not be hit if logic for adding actions is correct
                     println!("Streaming link is not available. Try 'Stream this video' first.");
                 }
                 // Continue inner loop
             }
+            Some("Favorite") | Some("Remove from favorites") => {
+                let favorite = preferences.toggle_favorite(selected_file);
+                if let Err(e) = preferences.save(None) {
+                    eprintln!("Error saving favorites: {}", e);
+                }
+                if favorite {
+                    println!("Added '{}' to favorites.", selected_file.display());
+                } else {
+                    println!("Removed '{}' from favorites.", selected_file.display());
+                }
+                // Continue inner loop
+            }
+            Some("Rate this video") => {
+                if let Some(rating) = prompt_rating(selected_video_entry.rating, theme)? {
+                    match history.rate_latest_pick(selected_file, rating) {
+                        Ok(true) => println!("Rated {}.", format_rating(rating)),
+                        Ok(false) => println!("Only picked videos can be rated."),
+                        Err(e) => eprintln!("Error saving rating: {}", e),
+                    }
+                }
+                // Continue inner loop
+            }
+            Some("Never show again") => {
+                preferences.block(selected_file);
+                if let Err(e) = preferences.save(None) {
+                    eprintln!("Error saving blocklist: {}", e);
+                }
+                println!("'{}' will not be picked again.", selected_file.display());
+                return Ok(PostActionOutcome::PickAnotherFromThisFolder);
+            }
             Some("Pick another from this folder") => {
                 return Ok(PostActionOutcome::PickAnotherFromThisFolder)
             }
             Some("Rescan current folder") => return Ok(PostActionOutcome::RescanCurrentFolder),
+            Some("Filter candidates") => {
+                edit_candidate_filter(candidate_filter, theme)?;
+                println!("Active filters: {}. They apply from the next pick.", candidate_filter);
+                // Continue inner loop
+            }
+            Some("Change selection strategy") => {
+                *strategy_kind = choose_strategy(*strategy_kind, theme)?;
+                println!("Selection strategy: {}. It applies from the next pick.", strategy_kind);
+                // Continue inner loop
+            }
             Some("Choose a different folder") => {
                 return Ok(PostActionOutcome::ChooseDifferentFolder)
             }
             Some("View history") => {
-                view_history(history, theme)?; // Display history
+                view_history(&history.store().load()?, theme)?; // Display history
                                                // Continue inner loop
             }
             Some("Quit") | Some(_) | None => return Ok(PostActionOutcome::QuitApplication), // Quit or any other unhandled
@@ -522,6 +951,232 @@ async fn loop_user_actions(
     } // End of 'inner loop
 }
 
+/// Handles the actions for a drawn playlist: playing it, streaming one of its videos,
+/// or exporting it, until the user moves on.
+fn loop_playlist_actions(
+    playlist: &Playlist,
+    theme: &ColorfulTheme,
+    stream_state_arc: &Option<StreamState>,
+    stream_url_base: &Option<String>,
+) -> Result<PostActionOutcome, Box<dyn std::error::Error>> {
+    loop {
+        let mut actions = vec!["Play all in order"];
+        if stream_state_arc.is_some() && stream_url_base.is_some() {
+            actions.push("Stream a video from the list");
+        }
+        actions.extend(vec![
+            "Export as M3U playlist",
+            "Make another playlist",
+            "Rescan current folder",
+            "Choose a different folder",
+            "Quit",
+        ]);
+
+        let choice_idx = Select::with_theme(theme)
+            .with_prompt("Playlist ready. What next?")
+            .items(&actions)
+            .default(0)
+            .interact_opt()?
+            .unwrap_or(actions.len() - 1); // Quit if Esc
+
+        match actions.get(choice_idx).copied() {
+            Some("Play all in order") => {
+                // Hand the whole list to the default player as a temporary M3U file.
+                let m3u_path = env::temp_dir().join(PLAYLIST_TEMP_FILE_NAME);
+                match playlist.write_m3u(&m3u_path) {
+                    Ok(()) => {
+                        if let Err(e) = play_video_locally(&m3u_path) {
+                            eprintln!("Error playing playlist locally: {}", e);
+                        }
+                    }
+                    Err(e) => eprintln!("Error writing playlist file: {}", e),
+                }
+            }
+            Some("Stream a video from the list") => {
+                let items: Vec<String> = playlist
+                    .items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| format!("{}. {}", i + 1, item.entry.path.display()))
+                    .collect();
+                let selection = Select::with_theme(theme)
+                    .with_prompt("Which video should be streamed?")
+                    .items(&items)
+                    .default(0)
+                    .interact_opt()?;
+                if let (Some(index), Some(state), Some(base_url)) =
+                    (selection, stream_state_arc, stream_url_base)
+                {
+                    let video_path = &playlist.items[index].entry.path;
+                    *state.lock().unwrap() = Some(video_path.clone());
+                    show_streaming_url(video_path, &format!("{}/stream", base_url));
+                }
+            }
+            Some("Export as M3U playlist") => {
+                let export_path: String = Input::with_theme(theme)
+                    .with_prompt("Save playlist as")
+                    .default("playlist.m3u".to_string())
+                    .interact_text()?;
+                let export_path = PathBuf::from(shellexpand::tilde(&export_path).into_owned());
+                match playlist.write_m3u(&export_path) {
+                    Ok(()) => println!("Playlist saved to '{}'.", export_path.display()),
+                    Err(e) => eprintln!("Error saving playlist: {}", e),
+                }
+            }
+            Some("Make another playlist") => {
+                return Ok(PostActionOutcome::PickAnotherFromThisFolder)
+            }
+            Some("Rescan current folder") => return Ok(PostActionOutcome::RescanCurrentFolder),
+            Some("Choose a different folder") => {
+                return Ok(PostActionOutcome::ChooseDifferentFolder)
+            }
+            Some("Quit") | Some(_) | None => return Ok(PostActionOutcome::QuitApplication),
+        }
+    }
+}
+
+/// Updates the folder state for the outcome of the action menu and tells the main loop
+/// whether to continue.
+fn handle_post_action_outcome(
+    action_outcome: PostActionOutcome,
+    current_folders_opt: &mut Option<Vec<PathBuf>>,
+    cached_folder_scan: &mut Option<CachedScan>,
+) -> LoopControl {
+    match action_outcome {
+        PostActionOutcome::PickAnotherFromThisFolder => {
+            // current_folders_opt is already set to the current folders.
+            // Cache will be used if still valid for this folder.
+            LoopControl::Continue
+        }
+        PostActionOutcome::RescanCurrentFolder => {
+            // current_folders_opt should still be the current folders.
+            // Drop the in-memory list so the next scan refreshes the library index,
+            // which only re-reads directories that changed since the last scan.
+            if current_folders_opt.is_some() {
+                *cached_folder_scan = None;
+            } else {
+                // This state should ideally not be reached if "Rescan" was an option.
+                log::error!("'Rescan current folder' chosen, but no current folder path is set. Prompting for new folder.");
+                *current_folders_opt = None; // Force re-prompt
+                *cached_folder_scan = None;
+            }
+            LoopControl::Continue
+        }
+        PostActionOutcome::ChooseDifferentFolder => {
+            *current_folders_opt = None; // Clear current folders to trigger prompt
+            *cached_folder_scan = None; // Clear cache as folder is changing
+            LoopControl::Continue
+        }
+        PostActionOutcome::QuitApplication => {
+            LoopControl::Break // Exit the main application loop
+        }
+    }
+}
+
+/// Runs the `duplicates` subcommand: scans the library and lists groups of identical files.
+fn run_duplicates_command(
+    cli_args: &Cli,
+    theme: &ColorfulTheme,
+    scan_options: &ScanOptions,
+    library_index: &mut Option<LibraryIndex>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut current_folders_opt = determine_initial_folders(cli_args);
+    let mut cached_folder_scan = None;
+    let folders = get_or_prompt_folders(&current_folders_opt, theme, &mut cached_folder_scan)?;
+    let folders_to_scan =
+        validate_folders(&folders, &mut current_folders_opt, &mut cached_folder_scan);
+    if folders_to_scan.is_empty() {
+        return Err("No accessible folders to scan.".into());
+    }
+    let videos = scan_for_videos(
+        &folders_to_scan,
+        scan_options,
+        &mut cached_folder_scan,
+        &mut current_folders_opt,
+        library_index,
+        false, // Nothing to keep up to date after listing.
+    )?;
+
+    let mode = if cli_args.full_hash {
+        HashMode::Full
+    } else {
+        HashMode::Partial
+    };
+    println!("Comparing {} videos...", videos.len());
+    let duplicates = DuplicateIndex::find(&videos, mode, &mut HashCache::new());
+    show_duplicate_groups(duplicates.groups());
+    Ok(())
+}
+
+/// Runs the `history` subcommands.
+fn run_history_command(
+    command: &HistoryCommand,
+    settings: &Settings,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        HistoryCommand::Import { file } => {
+            let source = JsonHistoryStore::open(file.as_deref())?;
+            let entries = source.load()?;
+            let database_path = get_history_database_path()?;
+            let mut store = SqliteHistoryStore::open(&database_path)?;
+            let imported = store.import(&entries)?;
+
+            // Every pick in the file must now be in the database (which may hold more)
+            let stored_counts = store.pick_counts()?;
+            for (path, count) in source.pick_counts()? {
+                let stored = stored_counts.get(&path).copied().unwrap_or(0);
+                if stored < count {
+                    return Err(format!(
+                        "Import incomplete: '{}' has {} picks in the file but {} in the database.",
+                        path, count, stored
+                    )
+                    .into());
+                }
+            }
+            println!(
+                "Imported {} of {} picks into '{}' ({} were already there).",
+                imported,
+                entries.len(),
+                database_path.display(),
+                entries.len() - imported
+            );
+            if settings.history_backend != HistoryBackend::Sqlite {
+                println!(
+                    "To use it, set \"history_backend\": \"sqlite\" in '{}'.",
+                    get_settings_path()?.display()
+                );
+            }
+            Ok(())
+        }
+        HistoryCommand::Prune { dry_run } => {
+            let policy = &settings.history_retention;
+            if policy.is_empty() {
+                println!(
+                    "No retention policy is set, so the whole history is kept. Set \
+                     \"history_retention\" in '{}', e.g. {{ \"keep_last\": 5000 }}.",
+                    get_settings_path()?.display()
+                );
+                return Ok(());
+            }
+            let mut store: Box<dyn HistoryStore> = match settings.history_backend {
+                HistoryBackend::Json => Box::new(JsonHistoryStore::open(None)?),
+                HistoryBackend::Sqlite => {
+                    Box::new(SqliteHistoryStore::open(&get_history_database_path()?)?)
+                }
+            };
+            println!("Retention policy: {}.", policy);
+            let now = Utc::now();
+            let plan = if *dry_run {
+                store.plan_prune(policy, now)?
+            } else {
+                store.prune(policy, now)?
+            };
+            show_prune_plan(&plan, *dry_run);
+            Ok(())
+        }
+    }
+}
+
 /// Stops the streaming server if it's running.
 /// Includes a timeout to prevent the application from hanging.
 async fn shutdown_streaming_server_logic(server_handle: ServerHandle) {
@@ -536,27 +1191,77 @@ async fn shutdown_streaming_server_logic(server_handle: ServerHandle) {
 /// Main application logic, orchestrating the video picking process.
 async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
     // 1. Initialization
-    let (cli_args, theme, mut history) = initialize_app_state()?;
+    let (cli_args, theme) = initialize_app_state();
+    let mut library_index = load_library_index(cli_args.no_index)?;
+    let settings = Settings::load(None)?;
+    let scan_options = build_scan_options(&cli_args, &settings)?;
+
+    match &cli_args.command {
+        Some(Command::Duplicates) => {
+            return run_duplicates_command(&cli_args, &theme, &scan_options, &mut library_index);
+        }
+        Some(Command::History { command }) => return run_history_command(command, &settings),
+        None => {}
+    }
+    let mut history = open_history(&settings, &theme)?;
 
     // 2. Setup Streaming Server
     let streaming_components_opt = setup_streaming_server_logic(cli_args.no_streaming).await?;
     let (mut actix_server_main_handle, stream_state_arc, stream_url_base) =
         destructure_streaming_components(streaming_components_opt);
 
-    // 3. Initial Folder Path & Scan Configuration
-    let mut current_folder_path_opt: Option<PathBuf> = determine_initial_folder_path(&cli_args);
-    let scan_recursively = !cli_args.non_recursive;
-    let mut cached_folder_scan: Option<(PathBuf, Vec<PathBuf>)> = None;
+    // 3. Initial Folder Path & Selection Configuration
+    let mut current_folders_opt: Option<Vec<PathBuf>> = determine_initial_folders(&cli_args);
+    let detect_duplicates = cli_args.detect_duplicates || settings.detect_duplicates;
+    let hash_mode = if cli_args.full_hash {
+        HashMode::Full
+    } else {
+        HashMode::Partial
+    };
+    let mut hash_cache = HashCache::new();
+    let mut duplicate_cache = DuplicateCache::default();
+    let no_duplicates = DuplicateIndex::default();
+    let mut strategy_options = StrategyOptions {
+        folder_weights: settings.folder_weights.clone(),
+        ..StrategyOptions::default()
+    };
+    if let Some(half_life) = cli_args.half_life {
+        strategy_options.half_life = half_life;
+    }
+    if let Some(depth) = cli_args.folder_depth.or(settings.folder_depth) {
+        strategy_options.folder_depth = depth;
+    }
+    let repeat_window = RepeatWindow {
+        last_picks: cli_args.no_repeat_picks,
+        within: cli_args.no_repeat_within,
+    };
+    let mut preferences = VideoPreferences::load(None)?;
+    if let Some(weight) = cli_args.favorite_weight.or(settings.favorite_weight) {
+        preferences.favorite_weight = weight;
+    }
+    let mut strategy_kind = cli_args.strategy;
+    let mut strategy = strategy_kind.build(&strategy_options);
+    let seed = cli_args.seed.unwrap_or_else(rand::random);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut pick_number = 0usize;
+    let playlist_target = PlaylistTarget {
+        count: cli_args.playlist,
+        duration: cli_args.playlist_duration,
+    };
+    let mut candidate_filter = build_candidate_filter(&cli_args);
+    let mut duration_cache = DurationCache::new();
+    let mut candidate_cache = CandidateCache::default();
+    let mut cached_folder_scan: Option<CachedScan> = None;
 
     // 4. Main Application Loop
     'outer: loop {
-        // 4.1. Determine Folder to Scan (Prompt if necessary)
-        let folder_to_scan = match get_or_prompt_folder_path(
-            &current_folder_path_opt, // Pass as immutable ref
+        // 4.1. Determine Folders to Scan (Prompt if necessary)
+        let folders = match get_or_prompt_folders(
+            &current_folders_opt, // Pass as immutable ref
             &theme,
             &mut cached_folder_scan,
         ) {
-            Ok(path) => path,
+            Ok(folders) => folders,
             Err(e) => {
                 // Error during folder prompt (e.g., user cancellation)
                 log::info!(
@@ -567,41 +1272,86 @@ async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
             }
         };
 
-        // 4.2. Validate Folder Path
-        if !validate_folder_path(
-            &folder_to_scan,
-            &mut current_folder_path_opt,
-            &mut cached_folder_scan,
-        ) {
-            continue 'outer; // Validation failed, current_folder_path_opt is now None, will re-prompt
+        // 4.2. Validate Folders
+        let folders_to_scan =
+            validate_folders(&folders, &mut current_folders_opt, &mut cached_folder_scan);
+        if folders_to_scan.is_empty() {
+            continue 'outer; // Validation failed, current_folders_opt is now None, will re-prompt
         }
-        // If validation passed and we got here via prompt, current_folder_path_opt might still be None.
-        // Set it to the successfully validated folder_to_scan.
-        current_folder_path_opt = Some(folder_to_scan.clone());
+        // If validation passed and we got here via prompt, current_folders_opt might still be None.
+        // Set it to the requested folders; inaccessible ones are retried on the next rescan.
+        current_folders_opt = Some(folders);
 
         // 4.3. Scan for Video Files (with caching)
         let video_files_paths = match scan_for_videos(
-            &folder_to_scan, // This is now guaranteed to be a valid directory path
-            scan_recursively,
+            &folders_to_scan, // These are now guaranteed to be valid directory paths
+            &scan_options,
             &mut cached_folder_scan,
-            &mut current_folder_path_opt, // Pass mutably to allow reset on scan error
+            &mut current_folders_opt, // Pass mutably to allow reset on scan error
+            &mut library_index,
+            !cli_args.no_watch,
         ) {
             Ok(paths) => paths,
-            Err(_) => continue 'outer, // Error during scan, current_folder_path_opt reset, will re-prompt
+            Err(_) => continue 'outer, // Error during scan, current_folders_opt reset, will re-prompt
         };
 
-        // At this point, current_folder_path_opt should reflect folder_to_scan
+        // At this point, current_folders_opt should reflect folders_to_scan
         // as scan_for_videos would have used it or it was set before.
         // Ensure it's updated for "Pick another from this folder" to work correctly.
-        // This was already handled by validate_folder_path and the logic in get_or_prompt_folder_path.
+        // This was already handled by validate_folders and the logic in get_or_prompt_folders.
 
         // 4.4. Handle No Videos Found
         if video_files_paths.is_empty() {
             match handle_no_videos_found_action_logic(
-                &folder_to_scan.to_string_lossy(),
+                &describe_folders(&folders_to_scan),
+                &theme,
+                history.store(),
+                &mut current_folders_opt,
+                &mut cached_folder_scan,
+            )? {
+                LoopControl::Continue => continue 'outer,
+                LoopControl::Break => break 'outer,
+            }
+        }
+
+        // 4.5. Update the Strategy, Group Identical Copies and Apply the Candidate Filters
+        strategy.library_changed(&folders_to_scan, &video_files_paths);
+        let duplicates = if detect_duplicates {
+            duplicate_cache.get(&video_files_paths, hash_mode, &mut hash_cache)
+        } else {
+            &no_duplicates
+        };
+        // Blocked videos (and every copy of them) are never candidates
+        let allowed: Vec<PathBuf> = video_files_paths
+            .iter()
+            .filter(|path| !preferences.is_any_blocked(duplicates.copies(path)))
+            .cloned()
+            .collect();
+        if allowed.len() < video_files_paths.len() {
+            log::info!(
+                "Blocklist excluded {} videos.",
+                video_files_paths.len() - allowed.len()
+            );
+        }
+        let candidates = candidate_cache.get(
+            &candidate_filter,
+            &allowed,
+            SystemTime::now(),
+            &mut duration_cache,
+        );
+        if !candidate_filter.is_empty() {
+            println!(
+                "{} of {} videos match the active filters ({}).",
+                candidates.len(),
+                allowed.len(),
+                candidate_filter
+            );
+        }
+        if candidates.is_empty() {
+            match handle_no_matching_videos_logic(
                 &theme,
-                &history, // Pass immutable slice
-                &mut current_folder_path_opt,
+                &mut candidate_filter,
+                &mut current_folders_opt,
                 &mut cached_folder_scan,
             )? {
                 LoopControl::Continue => continue 'outer,
@@ -609,65 +1359,125 @@ async fn run_app() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
-        // 4.5. Select a Video
-        let selected_video_entry = match select_video_logic(&video_files_paths, &history) {
+        // 4.6. Draw a Playlist Instead, if One Was Asked For
+        if !playlist_target.is_empty() {
+            let first_pick = pick_number + 1;
+            let playlist = match Playlist::build(
+                playlist_target,
+                &candidates,
+                duplicates,
+                |remaining| {
+                    let entry = select_video_logic(
+                        remaining,
+                        &history,
+                        duplicates,
+                        &repeat_window,
+                        &preferences,
+                        strategy.as_mut(),
+                        &mut rng,
+                    )?;
+                    history.record_pick(&entry.path)?; // Each draw is a pick
+                    pick_number += 1;
+                    Ok(entry)
+                },
+                |path| {
+                    *duration_cache.entry(path.to_path_buf()).or_insert_with(|| {
+                        get_video_metadata(path).ok().and_then(|metadata| metadata.duration_secs)
+                    })
+                },
+            ) {
+                Ok(playlist) => playlist,
+                Err(e) => {
+                    log::error!(
+                        "Failed to draw a playlist from {}: {}",
+                        describe_folders(&folders_to_scan),
+                        e
+                    );
+                    eprintln!("Could not draw a playlist: {}", e);
+                    current_folders_opt = None;
+                    cached_folder_scan = None;
+                    continue 'outer;
+                }
+            };
+            show_playlist(&playlist, playlist_target);
+            println!(
+                "Seed: {} (picks #{}-#{} this session; replay with --seed {})",
+                seed, first_pick, pick_number, seed
+            );
+            let action_outcome =
+                loop_playlist_actions(&playlist, &theme, &stream_state_arc, &stream_url_base)?;
+            match handle_post_action_outcome(
+                action_outcome,
+                &mut current_folders_opt,
+                &mut cached_folder_scan,
+            ) {
+                LoopControl::Continue => continue 'outer,
+                LoopControl::Break => break 'outer,
+            }
+        }
+
+        // 4.7. Select a Video
+        let selected_video_entry = match select_video_logic(
+            &candidates,
+            &history,
+            duplicates,
+            &repeat_window,
+            &preferences,
+            strategy.as_mut(),
+            &mut rng,
+        ) {
             Ok(entry) => entry,
             Err(e) => {
                 log::error!(
-                    "Failed to select a video from '{}': {}. Video files found: {}.",
-                    folder_to_scan.display(),
+                    "Failed to select a video from {}: {}. Video files found: {}.",
+                    describe_folders(&folders_to_scan),
                     e,
-                    video_files_paths.len()
+                    candidates.len()
                 );
                 eprintln!("Could not select a video: {}", e);
                 // To recover, try prompting for a folder again.
-                current_folder_path_opt = None;
+                current_folders_opt = None;
                 cached_folder_scan = None;
                 continue 'outer;
             }
         };
 
-        display_selected_video_info(&selected_video_entry);
-        add_to_history(&mut history, &selected_video_entry.path, None)?; // Update history
+        pick_number += 1;
+        display_selected_video_info(&selected_video_entry, &folders_to_scan, duplicates);
+        println!(
+            "Seed: {} (pick #{} this session; replay with --seed {})",
+            seed, pick_number, seed
+        );
+        history.record_pick(&selected_video_entry.path)?; // Update history
 
-        // 4.6. Handle User Actions for the Selected Video (Inner Loop)
+        // 4.8. Handle User Actions for the Selected Video (Inner Loop)
+        let previous_strategy_kind = strategy_kind;
         let action_outcome = loop_user_actions(
             &selected_video_entry,
-            &history, // Pass immutable slice of updated history
+            &mut history, // Updated history; ratings are saved into it
             &theme,
+            MenuSettings {
+                candidate_filter: &mut candidate_filter,
+                strategy_kind: &mut strategy_kind,
+                preferences: &mut preferences,
+            },
             &stream_state_arc,
             &stream_url_base,
         )
         .await?;
+        if strategy_kind != previous_strategy_kind {
+            strategy = strategy_kind.build(&strategy_options);
+            // The new strategy learns about the library on the next pick's scan.
+        }
 
-        // 4.7. Process Outcome of Inner Loop
-        match action_outcome {
-            PostActionOutcome::PickAnotherFromThisFolder => {
-                // current_folder_path_opt is already set to the current folder.
-                // Cache will be used if still valid for this folder.
-                continue 'outer;
-            }
-            PostActionOutcome::RescanCurrentFolder => {
-                // current_folder_path_opt should still be the current folder.
-                // Invalidate cache for this specific folder to force a fresh scan.
-                if current_folder_path_opt.is_some() {
-                    cached_folder_scan = None;
-                } else {
-                    // This state should ideally not be reached if "Rescan" was an option.
-                    log::error!("'Rescan current folder' chosen, but no current folder path is set. Prompting for new folder.");
-                    current_folder_path_opt = None; // Force re-prompt
-                    cached_folder_scan = None;
-                }
-                continue 'outer;
-            }
-            PostActionOutcome::ChooseDifferentFolder => {
-                current_folder_path_opt = None; // Clear current folder to trigger prompt
-                cached_folder_scan = None; // Clear cache as folder is changing
-                continue 'outer;
-            }
-            PostActionOutcome::QuitApplication => {
-                break 'outer; // Exit the main application loop
-            }
+        // 4.9. Process Outcome of Inner Loop
+        match handle_post_action_outcome(
+            action_outcome,
+            &mut current_folders_opt,
+            &mut cached_folder_scan,
+        ) {
+            LoopControl::Continue => continue 'outer,
+            LoopControl::Break => break 'outer,
         }
     } // End of 'outer loop
 
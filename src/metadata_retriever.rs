@@ -61,6 +61,8 @@ pub struct VideoMetadata {
     pub resolution: Option<String>,
     /// Video duration (e.g., "01:23:45" or "MM:SS"). Optional.
     pub duration: Option<String>,
+    /// Video duration in seconds, for filtering. Optional.
+    pub duration_secs: Option<f64>,
 }
 
 // Internal structs for parsing ffprobe JSON output.
@@ -161,6 +163,11 @@ pub fn parse_ffprobe_output(json_str: &str) -> Result<VideoMetadata, Box<dyn std
 
     if let Some(duration_str) = duration_str_opt {
         video_info.duration = format_duration_string(duration_str);
+        video_info.duration_secs = duration_str
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|secs| *secs >= 0.0);
     }
 
     Ok(video_info)
@@ -294,6 +301,7 @@ mod tests {
         let metadata = parse_ffprobe_output(json).unwrap();
         assert_eq!(metadata.resolution, Some("1920x1080".to_string()));
         assert_eq!(metadata.duration, Some("01:40".to_string()));
+        assert_eq!(metadata.duration_secs, Some(100.0));
     }
 
     #[test]
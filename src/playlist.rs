@@ -0,0 +1,247 @@
+// src/playlist.rs
+
+use crate::duplicate_finder::DuplicateIndex;
+use crate::video_entry::VideoEntry;
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// When to stop drawing videos for a playlist. With both bounds set, drawing stops at
+/// whichever is reached first; with neither, no playlist is made.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PlaylistTarget {
+    /// Stop after this many videos.
+    pub count: Option<usize>,
+    /// Stop once the videos add up to at least this long.
+    pub duration: Option<Duration>,
+}
+
+impl PlaylistTarget {
+    /// Returns true if no playlist was asked for.
+    pub fn is_empty(&self) -> bool {
+        self.count.is_none() && self.duration.is_none()
+    }
+
+    /// Returns true if a playlist of `len` videos lasting `total_secs` is complete.
+    pub fn is_reached(&self, len: usize, total_secs: f64) -> bool {
+        self.count.is_some_and(|count| len >= count)
+            || self
+                .duration
+                .is_some_and(|duration| total_secs >= duration.as_secs_f64())
+    }
+}
+
+impl fmt::Display for PlaylistTarget {
+    /// Describes the target, e.g. `5 videos, 2h`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(count) = self.count {
+            parts.push(format!("{} videos", count));
+        }
+        if let Some(duration) = self.duration {
+            parts.push(humantime::format_duration(duration).to_string());
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// One video in a playlist.
+#[derive(Debug, Clone)]
+pub struct PlaylistItem {
+    /// The picked video.
+    pub entry: VideoEntry,
+    /// Its duration in seconds, if it could be determined.
+    pub duration_secs: Option<f64>,
+}
+
+/// A list of distinct videos drawn one after another, to be watched in order.
+#[derive(Debug, Clone, Default)]
+pub struct Playlist {
+    /// The videos in the order they were drawn.
+    pub items: Vec<PlaylistItem>,
+}
+
+impl Playlist {
+    /// Draws videos until `target` is reached or no candidates are left. Each video is
+    /// drawn at most once: after a draw, it (and every identical copy of it) is removed
+    /// from the candidates passed to the next draw.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - When to stop drawing.
+    /// * `candidates` - The videos to draw from.
+    /// * `duplicates` - Identical copies, which count as one video.
+    /// * `draw` - Picks one video from the remaining candidates.
+    /// * `duration_of` - Returns a video's duration in seconds, if known. Videos of
+    ///   unknown duration count as zero towards a duration target.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error returned by `draw`.
+    pub fn build(
+        target: PlaylistTarget,
+        candidates: &[PathBuf],
+        duplicates: &DuplicateIndex,
+        mut draw: impl FnMut(&[PathBuf]) -> Result<VideoEntry, Box<dyn std::error::Error>>,
+        mut duration_of: impl FnMut(&Path) -> Option<f64>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut playlist = Self::default();
+        let mut remaining = candidates.to_vec();
+        while !remaining.is_empty() && !target.is_reached(playlist.len(), playlist.total_secs()) {
+            let entry = draw(&remaining)?;
+            let drawn = duplicates.canonical(&entry.path).to_path_buf();
+            remaining.retain(|path| duplicates.canonical(path) != drawn);
+            let duration_secs = duration_of(&entry.path);
+            if duration_secs.is_none() && target.duration.is_some() {
+                log::warn!(
+                    "Unknown duration for '{}'; it does not count towards the playlist length.",
+                    entry.path.display()
+                );
+            }
+            playlist.items.push(PlaylistItem {
+                entry,
+                duration_secs,
+            });
+        }
+        Ok(playlist)
+    }
+
+    /// The number of videos in the playlist.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// The summed duration of the videos whose duration is known, in seconds.
+    pub fn total_secs(&self) -> f64 {
+        self.items
+            .iter()
+            .filter_map(|item| item.duration_secs)
+            .sum()
+    }
+
+    /// Writes the playlist as an extended M3U file, which most players can open.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written.
+    pub fn write_m3u(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writeln!(writer, "#EXTM3U")?;
+        for item in &self.items {
+            let title = item.entry.path.file_stem().map_or_else(
+                || item.entry.path.to_string_lossy(),
+                |stem| stem.to_string_lossy(),
+            );
+            // -1 is the M3U convention for an unknown length.
+            let secs = item.duration_secs.map_or(-1, |secs| secs.round() as i64);
+            writeln!(writer, "#EXTINF:{},{}", secs, title)?;
+            writeln!(writer, "{}", item.entry.path.display())?;
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    fn videos(count: usize) -> Vec<PathBuf> {
+        (0..count)
+            .map(|i| PathBuf::from(format!("/videos/{}.mp4", i)))
+            .collect()
+    }
+
+    /// Draws the first remaining candidate.
+    fn first(remaining: &[PathBuf]) -> Result<VideoEntry, Box<dyn std::error::Error>> {
+        Ok(VideoEntry::new(remaining[0].clone(), 0))
+    }
+
+    #[test]
+    fn test_build_draws_distinct_videos_up_to_count() {
+        let candidates = videos(10);
+        let target = PlaylistTarget {
+            count: Some(5),
+            duration: None,
+        };
+        let playlist = Playlist::build(
+            target,
+            &candidates,
+            &DuplicateIndex::default(),
+            first,
+            |_| None,
+        )
+        .unwrap();
+        let paths: Vec<_> = playlist.items.iter().map(|item| &item.entry.path).collect();
+        assert_eq!(paths, candidates[..5].iter().collect::<Vec<_>>());
+
+        // Asking for more videos than there are stops when the candidates run out.
+        let target = PlaylistTarget {
+            count: Some(50),
+            duration: None,
+        };
+        let playlist = Playlist::build(
+            target,
+            &candidates,
+            &DuplicateIndex::default(),
+            first,
+            |_| None,
+        )
+        .unwrap();
+        assert_eq!(playlist.len(), 10);
+    }
+
+    #[test]
+    fn test_build_stops_at_target_duration() {
+        let candidates = videos(10);
+        let durations: HashMap<PathBuf, f64> = candidates
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != 1)
+            .map(|(_, path)| (path.clone(), 1800.0))
+            .collect();
+        let target = PlaylistTarget {
+            count: None,
+            duration: Some(Duration::from_secs(2 * 3600)),
+        };
+        let playlist = Playlist::build(
+            target,
+            &candidates,
+            &DuplicateIndex::default(),
+            first,
+            |path| durations.get(path).copied(),
+        )
+        .unwrap();
+        // Four half-hour videos, plus one of unknown length that does not count.
+        assert_eq!(playlist.len(), 5);
+        assert_eq!(playlist.total_secs(), 7200.0);
+    }
+
+    #[test]
+    fn test_write_m3u() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("evening.m3u");
+        let playlist = Playlist {
+            items: vec![
+                PlaylistItem {
+                    entry: VideoEntry::new(PathBuf::from("/videos/Movie.mkv"), 0),
+                    duration_secs: Some(5400.4),
+                },
+                PlaylistItem {
+                    entry: VideoEntry::new(PathBuf::from("/videos/Clip.mp4"), 0),
+                    duration_secs: None,
+                },
+            ],
+        };
+        playlist.write_m3u(&file).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "#EXTM3U\n#EXTINF:5400,Movie\n/videos/Movie.mkv\n#EXTINF:-1,Clip\n/videos/Clip.mp4\n"
+        );
+    }
+}
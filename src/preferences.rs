@@ -0,0 +1,229 @@
+// src/preferences.rs
+
+use crate::config::DEFAULT_FAVORITE_WEIGHT;
+use crate::file_utils::{atomic_write, get_preferences_path};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+};
+
+fn default_favorite_weight() -> f64 {
+    DEFAULT_FAVORITE_WEIGHT
+}
+
+/// Checks that `weight` can be used as a favorite weight: a finite number above 0.
+/// A weight of 0 or less would leave favorites with no valid selection weight.
+///
+/// # Errors
+///
+/// Returns a description of the problem if the weight is out of range.
+pub fn check_favorite_weight(weight: f64) -> Result<f64, String> {
+    if weight.is_finite() && weight > 0.0 {
+        Ok(weight)
+    } else {
+        Err(format!(
+            "invalid favorite weight '{}': expected a number above 0, e.g. 3",
+            weight
+        ))
+    }
+}
+
+/// Parses a favorite weight such as `3` or `1.5`; see `check_favorite_weight`.
+///
+/// # Errors
+///
+/// Returns a description of the problem if the weight cannot be parsed or is out of range.
+pub fn parse_favorite_weight(input: &str) -> Result<f64, String> {
+    let weight: f64 = input
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid favorite weight '{}': expected a number", input))?;
+    check_favorite_weight(weight)
+}
+
+/// The videos the user marked as favorites or never wants to see again, persisted in the
+/// app data directory.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct VideoPreferences {
+    /// Videos picked more often: their weight is multiplied by `favorite_weight`.
+    pub favorites: BTreeSet<PathBuf>,
+    /// Videos that are never picked.
+    pub blocked: BTreeSet<PathBuf>,
+    /// The weight multiplier for favorites. Comes from the settings, not the file.
+    #[serde(skip, default = "default_favorite_weight")]
+    pub favorite_weight: f64,
+}
+
+impl Default for VideoPreferences {
+    fn default() -> Self {
+        Self {
+            favorites: BTreeSet::new(),
+            blocked: BTreeSet::new(),
+            favorite_weight: DEFAULT_FAVORITE_WEIGHT,
+        }
+    }
+}
+
+impl VideoPreferences {
+    /// Loads the favorites and blocklist from disk.
+    /// If `custom_path` is provided, it uses that file instead of the default file.
+    /// If the file doesn't exist, empty sets are returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the preferences file path cannot be determined, the file
+    /// cannot be read, or it is not valid JSON. The file holds choices the user made one
+    /// by one, so it is reported rather than silently replaced.
+    pub fn load(custom_path: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let preferences_path_buf;
+        let preferences_path = match custom_path {
+            Some(p) => p,
+            None => {
+                preferences_path_buf = get_preferences_path()?;
+                &preferences_path_buf
+            }
+        };
+
+        match File::open(preferences_path) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file)).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Invalid favorites file '{}': {}",
+                        preferences_path.display(),
+                        e
+                    ),
+                )
+                .into()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// Saves the favorites and blocklist to disk.
+    /// If `custom_path` is provided, it saves to that file instead of the default file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the preferences file path cannot be determined, or if
+    /// I/O or serialization errors occur during saving. The file is replaced atomically,
+    /// so it keeps its previous contents if saving fails.
+    pub fn save(&self, custom_path: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+        let preferences_path_buf;
+        let preferences_path = match custom_path {
+            Some(p) => p,
+            None => {
+                preferences_path_buf = get_preferences_path()?;
+                &preferences_path_buf
+            }
+        };
+
+        atomic_write(preferences_path, |writer| {
+            serde_json::to_writer_pretty(writer, self)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    /// Returns true if `path` is a favorite.
+    pub fn is_favorite(&self, path: &Path) -> bool {
+        self.favorites.contains(path)
+    }
+
+    /// Returns true if `path` is on the blocklist.
+    pub fn is_blocked(&self, path: &Path) -> bool {
+        self.blocked.contains(path)
+    }
+
+    /// Returns true if any of `copies`, the identical copies of one video, is on the
+    /// blocklist.
+    pub fn is_any_blocked<'a>(&self, mut copies: impl Iterator<Item = &'a Path>) -> bool {
+        copies.any(|copy| self.is_blocked(copy))
+    }
+
+    /// Returns the factor the selection weight of a video is multiplied by, given its
+    /// identical `copies`. Favoriting any one of them counts for the video.
+    pub fn weight_multiplier<'a>(&self, mut copies: impl Iterator<Item = &'a Path>) -> f64 {
+        if copies.any(|copy| self.is_favorite(copy)) {
+            self.favorite_weight
+        } else {
+            1.0
+        }
+    }
+
+    /// Adds `path` to the favorites, or removes it if it already is one.
+    /// Returns true if `path` is now a favorite.
+    pub fn toggle_favorite(&mut self, path: &Path) -> bool {
+        if self.favorites.remove(path) {
+            false
+        } else {
+            self.favorites.insert(path.to_path_buf());
+            true
+        }
+    }
+
+    /// Puts `path` on the blocklist. A blocked video is no longer a favorite.
+    pub fn block(&mut self, path: &Path) {
+        self.favorites.remove(path);
+        self.blocked.insert(path.to_path_buf());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_toggle_block_and_round_trip() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("preferences.json");
+        let liked = PathBuf::from("/videos/liked.mp4");
+        let disliked = PathBuf::from("/videos/disliked.mp4");
+
+        let mut preferences = VideoPreferences::load(Some(&file)).unwrap();
+        assert_eq!(preferences, VideoPreferences::default());
+        assert!(preferences.toggle_favorite(&liked));
+        assert!(preferences.toggle_favorite(&disliked));
+        preferences.block(&disliked);
+        assert!(!preferences.is_favorite(&disliked));
+        preferences.save(Some(&file)).unwrap();
+
+        let mut loaded = VideoPreferences::load(Some(&file)).unwrap();
+        assert_eq!(loaded, preferences);
+        assert!(loaded.is_favorite(&liked));
+        assert!(loaded.is_blocked(&disliked));
+        let copy = PathBuf::from("/videos/liked (copy).mp4");
+        let copies = [copy.as_path(), liked.as_path()];
+        assert_eq!(loaded.weight_multiplier(copies.into_iter()), DEFAULT_FAVORITE_WEIGHT);
+        assert_eq!(loaded.weight_multiplier([disliked.as_path()].into_iter()), 1.0);
+        // Blocking one copy, not necessarily the one picked, blocks the video.
+        assert!(loaded.is_any_blocked([copy.as_path(), disliked.as_path()].into_iter()));
+        assert!(!loaded.is_any_blocked(copies.into_iter()));
+
+        assert!(!loaded.toggle_favorite(&liked));
+        assert!(!loaded.is_favorite(&liked));
+    }
+
+    #[test]
+    fn test_parse_favorite_weight() {
+        assert_eq!(parse_favorite_weight("1.5"), Ok(1.5));
+        assert_eq!(parse_favorite_weight(" 3 "), Ok(3.0));
+        for invalid in ["0", "-2", "NaN", "inf", "lots"] {
+            assert!(parse_favorite_weight(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_load_invalid_file() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("preferences.json");
+        std::fs::write(&file, "{ not json").unwrap();
+        assert!(VideoPreferences::load(Some(&file)).is_err());
+    }
+}
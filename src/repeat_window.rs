@@ -0,0 +1,233 @@
+// src/repeat_window.rs
+
+use crate::history_store::HistoryStore;
+use crate::video_entry::VideoEntry;
+use chrono::{DateTime, Utc};
+use std::{collections::HashSet, fmt, path::PathBuf, time::Duration};
+
+/// A hard exclusion of recently picked videos, applied before the selection strategy.
+/// A video is excluded if it is among the last `last_picks` history entries, or was
+/// picked within `within`. Both bounds are optional; with neither set nothing is excluded.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RepeatWindow {
+    /// Exclude videos picked in this many most recent picks.
+    pub last_picks: Option<usize>,
+    /// Exclude videos picked at most this long ago.
+    pub within: Option<Duration>,
+}
+
+/// The candidates left after applying a `RepeatWindow`.
+#[derive(Debug, Clone)]
+pub struct WindowedCandidates {
+    /// The entries to select from.
+    pub entries: Vec<VideoEntry>,
+    /// How many entries the window excluded.
+    pub excluded: usize,
+    /// True if the window would have excluded every entry and was relaxed to only
+    /// exclude the most recently picked one.
+    pub relaxed: bool,
+}
+
+impl RepeatWindow {
+    /// Returns true if the window excludes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.last_picks.is_none_or(|count| count == 0) && self.within.is_none()
+    }
+
+    /// Returns the paths in `history` that fall inside the window, queried from the store
+    /// so only the picks inside the window are read.
+    ///
+    /// # Arguments
+    ///
+    /// * `history` - The history store.
+    /// * `now` - The time the `within` bound is measured from.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the history store cannot be queried.
+    pub fn recent_paths(
+        &self,
+        history: &dyn HistoryStore,
+        now: DateTime<Utc>,
+    ) -> Result<HashSet<String>, Box<dyn std::error::Error>> {
+        let mut recent = HashSet::new();
+        if let Some(count) = self.last_picks {
+            let latest = history.recent_picks(count)?;
+            recent.extend(latest.into_iter().map(|entry| entry.path));
+        }
+        if let Some(within) = self.within {
+            let window = chrono::Duration::from_std(within).unwrap_or(chrono::Duration::MAX);
+            let cutoff = now
+                .checked_sub_signed(window)
+                .unwrap_or(DateTime::<Utc>::MIN_UTC);
+            let picks = history.picks_between(cutoff, now)?;
+            recent.extend(picks.into_iter().map(|entry| entry.path));
+        }
+        Ok(recent)
+    }
+
+    /// Removes the entries whose paths are in `recent` (as returned by `recent_paths`,
+    /// mapped to entry paths by the caller).
+    ///
+    /// If that would leave nothing to pick, the window is relaxed instead of failing:
+    /// only the most recently picked entry is excluded, so a small pool still never
+    /// repeats the previous pick back to back (unless it is the only video).
+    pub fn apply(&self, entries: Vec<VideoEntry>, recent: &HashSet<PathBuf>) -> WindowedCandidates {
+        let total = entries.len();
+        let (kept, excluded): (Vec<VideoEntry>, Vec<VideoEntry>) = entries
+            .into_iter()
+            .partition(|entry| !recent.contains(&entry.path));
+        if !kept.is_empty() || excluded.is_empty() {
+            return WindowedCandidates {
+                excluded: total - kept.len(),
+                entries: kept,
+                relaxed: false,
+            };
+        }
+
+        let latest = excluded
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, entry)| entry.last_picked)
+            .map(|(i, _)| i);
+        let mut entries = excluded;
+        if let (Some(i), true) = (latest, entries.len() > 1) {
+            entries.remove(i);
+        }
+        WindowedCandidates {
+            excluded: total - entries.len(),
+            entries,
+            relaxed: true,
+        }
+    }
+}
+
+impl fmt::Display for RepeatWindow {
+    /// Describes the window, e.g. `last 10 picks, last 3days`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(count) = self.last_picks.filter(|count| *count > 0) {
+            parts.push(format!("last {} picks", count));
+        }
+        if let Some(within) = self.within {
+            parts.push(format!("last {}", humantime::format_duration(within)));
+        }
+        if parts.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history_manager::HistoryEntry;
+    use crate::history_store::JsonHistoryStore;
+
+    fn picked(path: &str, hours_ago: i64, now: DateTime<Utc>) -> HistoryEntry {
+        HistoryEntry {
+            path: path.to_string(),
+            picked_at: now - chrono::Duration::hours(hours_ago),
+            rating: None,
+            count: 1,
+        }
+    }
+
+    /// Loads `picks` (newest first) through a JSON history store in `dir`.
+    fn history_store(dir: &std::path::Path, picks: &[HistoryEntry]) -> JsonHistoryStore {
+        let file = dir.join("history.json");
+        std::fs::write(&file, serde_json::to_string(picks).unwrap()).unwrap();
+        JsonHistoryStore::open(Some(&file)).unwrap()
+    }
+
+    fn entry(path: &str, last_picked: Option<DateTime<Utc>>) -> VideoEntry {
+        VideoEntry::with_picks(PathBuf::from(path), last_picked.into_iter().collect())
+    }
+
+    fn paths(recent: &HashSet<&str>) -> HashSet<PathBuf> {
+        recent.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_recent_paths() {
+        let now = Utc::now();
+        let dir = tempfile::tempdir().unwrap();
+        let history = history_store(
+            dir.path(),
+            &[
+                picked("a.mp4", 1, now),
+                picked("b.mp4", 30, now),
+                picked("c.mp4", 100, now),
+            ],
+        );
+        let expected = HashSet::from(["a.mp4".to_string(), "b.mp4".to_string()]);
+
+        let by_count = RepeatWindow {
+            last_picks: Some(2),
+            within: None,
+        };
+        assert_eq!(by_count.recent_paths(&history, now).unwrap(), expected);
+
+        let by_time = RepeatWindow {
+            last_picks: None,
+            within: Some(Duration::from_secs(2 * 86_400)),
+        };
+        assert_eq!(by_time.recent_paths(&history, now).unwrap(), expected);
+
+        let both = RepeatWindow {
+            last_picks: Some(1),
+            within: Some(Duration::from_secs(3_600 * 50)),
+        };
+        assert_eq!(both.recent_paths(&history, now).unwrap(), expected);
+        assert!(RepeatWindow::default()
+            .recent_paths(&history, now)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_apply_excludes_recent() {
+        let now = Utc::now();
+        let entries = vec![
+            entry("a.mp4", Some(now)),
+            entry("b.mp4", None),
+            entry("c.mp4", Some(now - chrono::Duration::days(1))),
+        ];
+        let window = RepeatWindow {
+            last_picks: Some(2),
+            within: None,
+        };
+        let recent = paths(&HashSet::from(["a.mp4", "c.mp4"]));
+        let result = window.apply(entries, &recent);
+        assert!(!result.relaxed);
+        assert_eq!(result.excluded, 2);
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].path, PathBuf::from("b.mp4"));
+    }
+
+    #[test]
+    fn test_apply_relaxes_when_everything_is_excluded() {
+        let now = Utc::now();
+        let entries = vec![
+            entry("a.mp4", Some(now)),
+            entry("b.mp4", Some(now - chrono::Duration::hours(2))),
+            entry("c.mp4", Some(now - chrono::Duration::hours(1))),
+        ];
+        let window = RepeatWindow {
+            last_picks: Some(10),
+            within: None,
+        };
+        let recent = paths(&HashSet::from(["a.mp4", "b.mp4", "c.mp4"]));
+        let result = window.apply(entries, &recent);
+        assert!(result.relaxed);
+        let kept: Vec<_> = result.entries.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(kept, vec![PathBuf::from("b.mp4"), PathBuf::from("c.mp4")]);
+
+        // A single video is still returned rather than leaving nothing to pick.
+        let result = window.apply(vec![entry("a.mp4", Some(now))], &recent);
+        assert!(result.relaxed);
+        assert_eq!(result.entries.len(), 1);
+    }
+}
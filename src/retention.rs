@@ -0,0 +1,303 @@
+// src/retention.rs
+
+use crate::candidate_filter::parse_duration;
+use crate::config::HISTORY_PRUNE_SLACK_PERCENT;
+use crate::history_manager::HistoryEntry;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    time::Duration,
+};
+
+/// How much of the pick history to keep. Configured with `history_retention` in the
+/// config file and applied as picks are saved, once the history has outgrown it by
+/// `HISTORY_PRUNE_SLACK_PERCENT`.
+///
+/// Picks outside either limit are dropped, or with `collapse` merged into one entry per
+/// video that keeps their count, so a video picked a hundred times years ago is still
+/// weighted as such.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    /// Keep only this many of the most recent picks.
+    pub keep_last: Option<usize>,
+    /// Keep only picks made at most this long ago, e.g. `"365d"`.
+    #[serde(
+        serialize_with = "serialize_duration",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub max_age: Option<Duration>,
+    /// Collapse the picks outside the limits into per-video counts instead of dropping them.
+    pub collapse: bool,
+}
+
+fn serialize_duration<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    duration
+        .map(|duration| humantime::format_duration(duration).to_string())
+        .serialize(serializer)
+}
+
+fn deserialize_duration<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|text| parse_duration(&text).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// What applying a retention policy takes out of the history and puts back.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrunePlan {
+    /// The entries taken out, newest first.
+    pub removed: Vec<HistoryEntry>,
+    /// With `collapse`, the entries replacing them: one per video, newest first.
+    pub collapsed: Vec<HistoryEntry>,
+}
+
+impl RetentionPolicy {
+    /// Returns true if the policy keeps everything.
+    pub fn is_empty(&self) -> bool {
+        self.keep_last.is_none() && self.max_age.is_none()
+    }
+
+    /// Returns the time before which picks are outside `max_age`, if it is set.
+    pub fn cutoff(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.max_age.map(|max_age| {
+            let max_age = chrono::Duration::from_std(max_age).unwrap_or(chrono::Duration::MAX);
+            now.checked_sub_signed(max_age)
+                .unwrap_or(DateTime::<Utc>::MIN_UTC)
+        })
+    }
+
+    /// Returns true if the history should be pruned now, given the `picks` saved since it
+    /// was last pruned at `last_pruned` (`None` if it has not been this session).
+    ///
+    /// Between prunes the history may hold `HISTORY_PRUNE_SLACK_PERCENT` more picks than
+    /// `keep_last`, and picks that expired up to that share of `max_age` ago.
+    pub fn prune_due(
+        &self,
+        picks: usize,
+        last_pruned: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        let Some(last_pruned) = last_pruned else {
+            return true;
+        };
+        let by_count = self.keep_last.is_some_and(|keep_last| {
+            picks >= (keep_last * HISTORY_PRUNE_SLACK_PERCENT as usize / 100).max(1)
+        });
+        let by_age = self.max_age.is_some_and(|max_age| {
+            let slack = max_age * HISTORY_PRUNE_SLACK_PERCENT / 100;
+            (now - last_pruned).to_std().is_ok_and(|elapsed| elapsed >= slack)
+        });
+        by_count || by_age
+    }
+
+    /// Returns the index from which the entries of `history`, sorted newest first, are
+    /// outside the policy.
+    pub fn expired_from(&self, history: &[HistoryEntry], now: DateTime<Utc>) -> usize {
+        let by_count = self.keep_last.unwrap_or(history.len()).min(history.len());
+        let by_age = self.cutoff(now).map_or(history.len(), |cutoff| {
+            history.partition_point(|entry| entry.picked_at >= cutoff)
+        });
+        by_count.min(by_age)
+    }
+
+    /// Plans what to do with the `expired` entries, sorted newest first.
+    ///
+    /// Without `collapse` they are all removed. With it, each video's expired entries
+    /// are merged into one, dated at the latest of them and keeping the latest rating; a
+    /// video with a single expired entry is left alone, as it is collapsed already.
+    pub fn plan(&self, expired: &[HistoryEntry]) -> PrunePlan {
+        if !self.collapse {
+            return PrunePlan {
+                removed: expired.to_vec(),
+                collapsed: Vec::new(),
+            };
+        }
+
+        let mut by_path: BTreeMap<&str, Vec<&HistoryEntry>> = BTreeMap::new();
+        for entry in expired {
+            by_path.entry(entry.path.as_str()).or_default().push(entry);
+        }
+        let mut plan = PrunePlan::default();
+        for entries in by_path.into_values().filter(|entries| entries.len() > 1) {
+            plan.collapsed.push(HistoryEntry {
+                path: entries[0].path.clone(),
+                picked_at: entries[0].picked_at,
+                rating: entries.iter().find_map(|entry| entry.rating),
+                count: entries.iter().map(|entry| entry.count).sum(),
+            });
+            plan.removed.extend(entries.into_iter().cloned());
+        }
+        plan.removed
+            .sort_by_key(|entry| std::cmp::Reverse(entry.picked_at));
+        plan.collapsed
+            .sort_by_key(|entry| std::cmp::Reverse(entry.picked_at));
+        plan
+    }
+}
+
+impl fmt::Display for RetentionPolicy {
+    /// Describes the policy, e.g. `last 1000 picks, last 1year, older picks collapsed`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(count) = self.keep_last {
+            parts.push(format!("last {} picks", count));
+        }
+        if let Some(max_age) = self.max_age {
+            parts.push(format!("last {}", humantime::format_duration(max_age)));
+        }
+        if parts.is_empty() {
+            return write!(f, "keep everything");
+        }
+        if self.collapse {
+            parts.push("older picks collapsed".to_string());
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl PrunePlan {
+    /// Returns true if the plan changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.collapsed.is_empty()
+    }
+
+    /// Applies the plan to `history`, sorted newest first.
+    pub fn apply_to(&self, history: &mut Vec<HistoryEntry>) {
+        if self.is_empty() {
+            return;
+        }
+        let removed: HashSet<(&str, DateTime<Utc>)> = self
+            .removed
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry.picked_at))
+            .collect();
+        history.retain(|entry| !removed.contains(&(entry.path.as_str(), entry.picked_at)));
+        history.extend(self.collapsed.iter().cloned());
+        history.sort_by_key(|entry| std::cmp::Reverse(entry.picked_at));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn picked(path: &str, day: u32) -> HistoryEntry {
+        HistoryEntry {
+            path: path.to_string(),
+            picked_at: Utc.with_ymd_and_hms(2024, 1, day, 20, 0, 0).unwrap(),
+            rating: None,
+            count: 1,
+        }
+    }
+
+    /// Applies `policy` on January 10th to `history`, sorted newest first.
+    fn prune(policy: RetentionPolicy, history: &[HistoryEntry]) -> Vec<HistoryEntry> {
+        let now = Utc.with_ymd_and_hms(2024, 1, 10, 0, 0, 0).unwrap();
+        let from = policy.expired_from(history, now);
+        let mut pruned = history.to_vec();
+        policy.plan(&history[from..]).apply_to(&mut pruned);
+        pruned
+    }
+
+    #[test]
+    fn test_keep_last_and_max_age() {
+        let history = vec![
+            picked("/d.mp4", 9),
+            picked("/c.mp4", 8),
+            picked("/b.mp4", 3),
+            picked("/a.mp4", 1),
+        ];
+        let keep_last = RetentionPolicy {
+            keep_last: Some(3),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(prune(keep_last, &history), history[..3]);
+
+        let max_age = RetentionPolicy {
+            max_age: Some(Duration::from_secs(5 * 86_400)),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(prune(max_age, &history), history[..2]);
+        assert_eq!(prune(RetentionPolicy::default(), &history), history);
+    }
+
+    #[test]
+    fn test_collapse_keeps_counts() {
+        let mut rated = picked("/a.mp4", 2);
+        rated.rating = Some(4);
+        let history = vec![
+            picked("/a.mp4", 9),
+            picked("/b.mp4", 4),
+            picked("/a.mp4", 3),
+            rated,
+            picked("/a.mp4", 1),
+        ];
+        let policy = RetentionPolicy {
+            keep_last: Some(2),
+            collapse: true,
+            ..RetentionPolicy::default()
+        };
+        let pruned = prune(policy, &history);
+        let collapsed = HistoryEntry {
+            rating: Some(4),
+            count: 3,
+            ..picked("/a.mp4", 3)
+        };
+        assert_eq!(pruned, [history[0].clone(), history[1].clone(), collapsed]);
+
+        // Collapsing again changes nothing until more picks expire.
+        let from = policy.expired_from(&pruned, Utc::now());
+        assert!(policy.plan(&pruned[from..]).is_empty());
+        let mut more = pruned.clone();
+        more.splice(0..0, [picked("/c.mp4", 11), picked("/c.mp4", 10)]);
+        let pruned = prune(policy, &more);
+        assert_eq!(pruned.len(), 4);
+        assert_eq!((pruned[2].path.as_str(), pruned[2].count), ("/a.mp4", 4));
+    }
+
+    #[test]
+    fn test_prune_due_after_slack() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 10, 0, 0, 0).unwrap();
+        let keep_last = RetentionPolicy {
+            keep_last: Some(1000),
+            ..RetentionPolicy::default()
+        };
+        assert!(keep_last.prune_due(0, None, now));
+        assert!(!keep_last.prune_due(99, Some(now), now));
+        assert!(keep_last.prune_due(100, Some(now), now));
+
+        let max_age = RetentionPolicy {
+            max_age: Some(Duration::from_secs(10 * 86_400)),
+            ..RetentionPolicy::default()
+        };
+        let yesterday = now - chrono::Duration::days(1);
+        assert!(!max_age.prune_due(1000, Some(now), now));
+        assert!(max_age.prune_due(1, Some(yesterday), now));
+        assert!(!RetentionPolicy::default().prune_due(1000, None, now));
+    }
+
+    #[test]
+    fn test_load_policy() {
+        let policy: RetentionPolicy =
+            serde_json::from_str(r#"{ "max_age": "365d", "collapse": true }"#).unwrap();
+        assert_eq!(policy.max_age, Some(Duration::from_secs(365 * 86_400)));
+        assert!(policy.collapse);
+        assert_eq!(policy.keep_last, None);
+        let round_trip: RetentionPolicy =
+            serde_json::from_str(&serde_json::to_string(&policy).unwrap()).unwrap();
+        assert_eq!(round_trip, policy);
+        assert!(serde_json::from_str::<RetentionPolicy>(r#"{ "max_age": "soon" }"#).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+// src/scan_filter.rs
+
+use crate::config::IGNORE_FILE_NAME;
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::gitignore::Gitignore;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Include/exclude glob patterns applied to paths relative to the scanned folder.
+///
+/// Patterns follow gitignore conventions: a pattern without a `/` (e.g. `sample.*` or
+/// `Extras`) matches a file or directory name at any depth, while a pattern containing
+/// a `/` (e.g. `Movies/**/*.mkv`) is matched against the whole relative path.
+/// Exclude patterns prune directories as well as files; include patterns only restrict
+/// which files are kept, so directories are always descended into.
+#[derive(Debug, Clone, Default)]
+pub struct ScanFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+/// Builds a glob for `pattern`, anchoring slash-less patterns to any depth.
+fn build_glob(pattern: &str) -> Result<Glob, globset::Error> {
+    let pattern = pattern.trim_end_matches('/');
+    let pattern = pattern.strip_prefix('/').map_or_else(
+        || {
+            if pattern.contains('/') {
+                pattern.to_string()
+            } else {
+                format!("**/{}", pattern)
+            }
+        },
+        str::to_string, // A leading slash anchors the pattern to the scanned folder.
+    );
+    GlobBuilder::new(&pattern).literal_separator(true).build()
+}
+
+/// Compiles a list of patterns into a `GlobSet`, or `None` if the list is empty.
+fn build_glob_set(patterns: &[String]) -> Result<Option<GlobSet>, globset::Error> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(build_glob(pattern)?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+impl ScanFilter {
+    /// Creates a filter from `--include` and `--exclude` patterns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any pattern is not a valid glob.
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, globset::Error> {
+        Ok(Self {
+            include: build_glob_set(include)?,
+            exclude: build_glob_set(exclude)?,
+        })
+    }
+
+    /// Returns true if the directory at `relative` should be descended into.
+    pub fn allows_dir(&self, relative: &Path) -> bool {
+        !self
+            .exclude
+            .as_ref()
+            .is_some_and(|set| set.is_match(relative))
+    }
+
+    /// Returns true if the file at `relative` should be kept.
+    pub fn allows_file(&self, relative: &Path) -> bool {
+        self.allows_dir(relative)
+            && self
+                .include
+                .as_ref()
+                .is_none_or(|set| set.is_match(relative))
+    }
+
+    /// Checks a single path under `root` against the globs and every `.pickerignore`
+    /// file between `root` and the path. This is meant for one-off checks (e.g. watcher
+    /// events); full scans apply the same rules incrementally while walking.
+    pub fn allows_path(&self, root: &Path, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(root) else {
+            return false;
+        };
+
+        let mut ignores = IgnoreRules::default();
+        let mut current = root.to_path_buf();
+        let components: Vec<_> = relative.components().collect();
+        for (i, component) in components.iter().enumerate() {
+            if current.join(IGNORE_FILE_NAME).is_file() {
+                ignores = ignores.with_ignore_file(&current);
+            }
+            current.push(component);
+            let is_last = i + 1 == components.len();
+            let relative_so_far = current.strip_prefix(root).unwrap_or(&current);
+            if is_last {
+                let is_dir = current.is_dir();
+                let allowed = if is_dir {
+                    self.allows_dir(relative_so_far)
+                } else {
+                    self.allows_file(relative_so_far)
+                };
+                return allowed && !ignores.is_ignored(&current, is_dir);
+            }
+            if !self.allows_dir(relative_so_far) || ignores.is_ignored(&current, true) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Gitignore-style rules from the `.pickerignore` files of a directory and its ancestors.
+/// Rules in deeper directories take precedence, as with nested `.gitignore` files.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    // Outermost directory first; shared so cloning per subdirectory is cheap.
+    matchers: Vec<Arc<Gitignore>>,
+}
+
+impl IgnoreRules {
+    /// Returns these rules extended with the `.pickerignore` file in `dir`.
+    /// Invalid lines are logged and skipped.
+    pub fn with_ignore_file(&self, dir: &Path) -> Self {
+        let ignore_path: PathBuf = dir.join(IGNORE_FILE_NAME);
+        let (matcher, err) = Gitignore::new(&ignore_path);
+        if let Some(e) = err {
+            log::warn!("Problem reading '{}': {}", ignore_path.display(), e);
+        }
+        let mut rules = self.clone();
+        if !matcher.is_empty() {
+            rules.matchers.push(Arc::new(matcher));
+        }
+        rules
+    }
+
+    /// Returns true if `path` is ignored by the innermost rule that matches it.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        for matcher in self.matchers.iter().rev() {
+            let matched = matcher.matched(path, is_dir);
+            if matched.is_ignore() {
+                return true;
+            }
+            if matched.is_whitelist() {
+                return false;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn patterns(list: &[&str]) -> Vec<String> {
+        list.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn test_exclude_matches_names_at_any_depth() {
+        let filter = ScanFilter::new(&[], &patterns(&["sample.*", "Extras", ".trash/"])).unwrap();
+        assert!(!filter.allows_file(Path::new("sample.mkv")));
+        assert!(!filter.allows_file(Path::new("Movie/sample.mkv")));
+        assert!(!filter.allows_dir(Path::new("Movie/Extras")));
+        assert!(!filter.allows_dir(Path::new(".trash")));
+        assert!(filter.allows_file(Path::new("Movie/movie.mkv")));
+        assert!(filter.allows_dir(Path::new("Movie")));
+    }
+
+    #[test]
+    fn test_include_and_anchored_patterns() {
+        let filter =
+            ScanFilter::new(&patterns(&["Movies/**/*.mkv"]), &patterns(&["/Movies/Old"])).unwrap();
+        assert!(filter.allows_file(Path::new("Movies/A/a.mkv")));
+        assert!(!filter.allows_file(Path::new("Movies/A/a.mp4")));
+        assert!(!filter.allows_file(Path::new("Shows/a.mkv")));
+        // Include patterns never prune directories.
+        assert!(filter.allows_dir(Path::new("Shows")));
+        assert!(!filter.allows_dir(Path::new("Movies/Old")));
+        assert!(filter.allows_dir(Path::new("Archive/Movies/Old")));
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_an_error() {
+        assert!(ScanFilter::new(&patterns(&["[unclosed"]), &[]).is_err());
+    }
+
+    #[test]
+    fn test_ignore_rules_nesting() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let show = root.join("Show");
+        fs::create_dir(&show).unwrap();
+        fs::write(root.join(IGNORE_FILE_NAME), "*.avi\nExtras/\n").unwrap();
+        fs::write(show.join(IGNORE_FILE_NAME), "!keep.avi\n").unwrap();
+
+        let root_rules = IgnoreRules::default().with_ignore_file(root);
+        assert!(root_rules.is_ignored(&root.join("a.avi"), false));
+        assert!(root_rules.is_ignored(&root.join("Extras"), true));
+        assert!(!root_rules.is_ignored(&root.join("Extras"), false)); // Directory-only rule.
+        assert!(!root_rules.is_ignored(&root.join("a.mkv"), false));
+
+        let show_rules = root_rules.with_ignore_file(&show);
+        assert!(!show_rules.is_ignored(&show.join("keep.avi"), false));
+        assert!(show_rules.is_ignored(&show.join("other.avi"), false));
+    }
+
+    #[test]
+    fn test_allows_path_checks_ancestors() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        let extras = root.join("Movie").join("Extras");
+        fs::create_dir_all(&extras).unwrap();
+        fs::write(root.join("Movie").join(IGNORE_FILE_NAME), "Extras/\n").unwrap();
+        fs::write(extras.join("featurette.mkv"), "").unwrap();
+        fs::write(root.join("Movie").join("movie.mkv"), "").unwrap();
+
+        let filter = ScanFilter::new(&[], &patterns(&["sample.*"])).unwrap();
+        assert!(!filter.allows_path(root, &extras.join("featurette.mkv")));
+        assert!(filter.allows_path(root, &root.join("Movie").join("movie.mkv")));
+        assert!(!filter.allows_path(root, &root.join("Movie").join("sample.mkv")));
+        assert!(!filter.allows_path(root, Path::new("/elsewhere/movie.mkv")));
+    }
+}
@@ -0,0 +1,446 @@
+// src/selection_strategy.rs
+
+use crate::config::{DEFAULT_FOLDER_DEPTH, DEFAULT_HALF_LIFE};
+use crate::folder_balance::FolderBalanced;
+use crate::series::NextEpisode;
+use crate::shuffle_bag::ShuffleBag;
+use crate::video_entry::VideoEntry;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use rand::prelude::*;
+use std::{collections::HashMap, fmt, path::PathBuf, time::Duration};
+
+/// An algorithm for choosing the next video from the candidates.
+pub trait SelectionStrategy {
+    /// Chooses one of `entries`, or returns `None` if `entries` is empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - The candidates, one per logical video, with their pick counts and times.
+    /// * `now` - The time of the pick, for strategies that weigh picks by age. Passed in
+    ///   rather than read from the clock, so a seeded selection is reproducible.
+    /// * `rng` - The source of randomness; strategies must not use any other.
+    fn select<'a>(
+        &mut self,
+        entries: &'a [VideoEntry],
+        now: DateTime<Utc>,
+        rng: &mut dyn Rng,
+    ) -> Option<&'a VideoEntry>;
+
+    /// Called after every scan with the library roots and the videos found under them,
+    /// before any filters are applied. Strategies that keep state about the library
+    /// use this to account for added and removed files.
+    fn library_changed(&mut self, _roots: &[PathBuf], _videos: &[PathBuf]) {}
+}
+
+/// The built-in selection strategies, as named on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum StrategyKind {
+    /// Weighted random choice; videos picked less often are more likely.
+    #[default]
+    Weighted,
+    /// Like `weighted`, but each past pick counts less as it ages (see `--half-life`).
+    Decayed,
+    /// Every video is equally likely.
+    Uniform,
+    /// The video whose last pick is longest ago (never-picked videos first).
+    LeastRecent,
+    /// A random never-picked video; weighted choice once every video has been picked.
+    NeverPickedFirst,
+    /// Every video in path order, continuing after the most recently picked one.
+    RoundRobin,
+    /// Every video once in random order before any repeats; remembered across restarts.
+    ShuffleBag,
+    /// A folder first (see `--folder-depth`), then a video in it, both weighted by picks.
+    FolderBalanced,
+    /// Each series counts as one video; picking it gives its next unwatched episode.
+    NextEpisode,
+}
+
+/// Tuning parameters for the built-in strategies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrategyOptions {
+    /// How long it takes for a pick's penalty to halve, for `StrategyKind::Decayed`.
+    pub half_life: Duration,
+    /// How many levels below the library root folders are balanced, for
+    /// `StrategyKind::FolderBalanced`.
+    pub folder_depth: usize,
+    /// Weight multipliers for folders (and everything below them), for
+    /// `StrategyKind::FolderBalanced`.
+    pub folder_weights: HashMap<PathBuf, f64>,
+}
+
+impl Default for StrategyOptions {
+    fn default() -> Self {
+        Self {
+            half_life: DEFAULT_HALF_LIFE,
+            folder_depth: DEFAULT_FOLDER_DEPTH,
+            folder_weights: HashMap::new(),
+        }
+    }
+}
+
+impl StrategyKind {
+    /// Creates the strategy this kind names.
+    pub fn build(self, options: &StrategyOptions) -> Box<dyn SelectionStrategy> {
+        match self {
+            StrategyKind::Weighted => Box::new(InverseCountWeighting),
+            StrategyKind::Decayed => Box::new(RecencyDecayedWeighting {
+                half_life: options.half_life,
+            }),
+            StrategyKind::Uniform => Box::new(UniformRandom),
+            StrategyKind::LeastRecent => Box::new(LeastRecentlyPicked),
+            StrategyKind::NeverPickedFirst => Box::new(NeverPickedFirst),
+            StrategyKind::RoundRobin => Box::new(RoundRobin),
+            StrategyKind::ShuffleBag => Box::new(ShuffleBag::new(None)),
+            StrategyKind::FolderBalanced => Box::new(FolderBalanced::new(
+                options.folder_depth,
+                options.folder_weights.clone(),
+            )),
+            StrategyKind::NextEpisode => Box::new(NextEpisode),
+        }
+    }
+
+    /// A short human-readable description, for menus.
+    pub fn description(self) -> &'static str {
+        match self {
+            StrategyKind::Weighted => "Weighted (less-picked videos more likely)",
+            StrategyKind::Decayed => "Weighted, with old picks gradually forgotten",
+            StrategyKind::Uniform => "Uniform random",
+            StrategyKind::LeastRecent => "Least recently picked",
+            StrategyKind::NeverPickedFirst => "Never-picked first",
+            StrategyKind::RoundRobin => "Round-robin in path order",
+            StrategyKind::ShuffleBag => "Shuffle bag (everything once before repeats)",
+            StrategyKind::FolderBalanced => "Folder-balanced (a folder first, then a video in it)",
+            StrategyKind::NextEpisode => "Next episode (series play in order)",
+        }
+    }
+}
+
+impl fmt::Display for StrategyKind {
+    /// Writes the name used by `--strategy`, e.g. `least-recent`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_possible_value() {
+            Some(value) => write!(f, "{}", value.get_name()),
+            None => write!(f, "{:?}", self),
+        }
+    }
+}
+
+/// Weighted random choice using `VideoEntry::weight`, i.e. `1 / (pick_count + 1)`.
+#[derive(Debug, Default)]
+pub struct InverseCountWeighting;
+
+impl SelectionStrategy for InverseCountWeighting {
+    fn select<'a>(
+        &mut self,
+        entries: &'a [VideoEntry],
+        _now: DateTime<Utc>,
+        rng: &mut dyn Rng,
+    ) -> Option<&'a VideoEntry> {
+        match entries.choose_weighted(rng, |item| item.weight()) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                if !entries.is_empty() {
+                    log::error!(
+                        "Error during weighted choice: {:?}. Video entries count: {}",
+                        e,
+                        entries.len()
+                    );
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Weighted random choice using `VideoEntry::decayed_weight`, so the penalty of each past
+/// pick halves every `half_life` and long-unwatched videos come back into rotation.
+#[derive(Debug)]
+pub struct RecencyDecayedWeighting {
+    /// How long it takes for a pick's penalty to halve.
+    pub half_life: Duration,
+}
+
+impl SelectionStrategy for RecencyDecayedWeighting {
+    fn select<'a>(
+        &mut self,
+        entries: &'a [VideoEntry],
+        now: DateTime<Utc>,
+        rng: &mut dyn Rng,
+    ) -> Option<&'a VideoEntry> {
+        entries
+            .choose_weighted(rng, |item| item.decayed_weight(now, self.half_life))
+            .ok()
+    }
+}
+
+/// Uniform random choice, ignoring history.
+#[derive(Debug, Default)]
+pub struct UniformRandom;
+
+impl SelectionStrategy for UniformRandom {
+    fn select<'a>(
+        &mut self,
+        entries: &'a [VideoEntry],
+        _now: DateTime<Utc>,
+        rng: &mut dyn Rng,
+    ) -> Option<&'a VideoEntry> {
+        entries.choose(rng)
+    }
+}
+
+/// Picks the video whose most recent pick is the oldest. Never-picked videos come first;
+/// ties (such as several never-picked videos) are broken at random.
+#[derive(Debug, Default)]
+pub struct LeastRecentlyPicked;
+
+impl SelectionStrategy for LeastRecentlyPicked {
+    fn select<'a>(
+        &mut self,
+        entries: &'a [VideoEntry],
+        _now: DateTime<Utc>,
+        rng: &mut dyn Rng,
+    ) -> Option<&'a VideoEntry> {
+        // `None` (never picked) sorts before every timestamp.
+        let oldest = entries.iter().map(|entry| entry.last_picked).min()?;
+        let tied: Vec<&VideoEntry> = entries
+            .iter()
+            .filter(|entry| entry.last_picked == oldest)
+            .collect();
+        tied.choose(rng).copied()
+    }
+}
+
+/// Picks uniformly among videos that were never picked; once every video has been
+/// picked at least once, falls back to inverse-count weighting.
+#[derive(Debug, Default)]
+pub struct NeverPickedFirst;
+
+impl SelectionStrategy for NeverPickedFirst {
+    fn select<'a>(
+        &mut self,
+        entries: &'a [VideoEntry],
+        now: DateTime<Utc>,
+        rng: &mut dyn Rng,
+    ) -> Option<&'a VideoEntry> {
+        let never_picked: Vec<&VideoEntry> = entries
+            .iter()
+            .filter(|entry| entry.pick_count == 0)
+            .collect();
+        match never_picked.choose(rng) {
+            Some(entry) => Some(*entry),
+            None => InverseCountWeighting.select(entries, now, rng),
+        }
+    }
+}
+
+/// Walks through the videos in path order, starting after the most recently picked one.
+/// The position is derived from the last-picked times, so it carries over between sessions.
+#[derive(Debug, Default)]
+pub struct RoundRobin;
+
+impl SelectionStrategy for RoundRobin {
+    fn select<'a>(
+        &mut self,
+        entries: &'a [VideoEntry],
+        _now: DateTime<Utc>,
+        _rng: &mut dyn Rng,
+    ) -> Option<&'a VideoEntry> {
+        let mut ordered: Vec<&VideoEntry> = entries.iter().collect();
+        ordered.sort_by(|a, b| a.path.cmp(&b.path));
+        let current = ordered
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entry)| entry.last_picked.map(|time| (time, i)))
+            .max()
+            .map(|(_, i)| i);
+        let next = current.map_or(0, |i| (i + 1) % ordered.len());
+        ordered.get(next).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history_manager::HistoryEntry;
+    use chrono::TimeZone;
+    use rand::rngs::StdRng;
+    use std::collections::HashMap;
+
+    fn make_entries(counts: &[(&str, usize)]) -> Vec<VideoEntry> {
+        counts
+            .iter()
+            .map(|(path, count)| VideoEntry::new(PathBuf::from(path), *count))
+            .collect()
+    }
+
+    /// Builds entries for `paths` with their picks taken from `history`.
+    fn entries_with_history(paths: &[&str], history: &[HistoryEntry]) -> Vec<VideoEntry> {
+        paths
+            .iter()
+            .map(|path| {
+                let picks = history
+                    .iter()
+                    .filter(|entry| entry.path == *path)
+                    .map(|entry| entry.picked_at)
+                    .collect();
+                VideoEntry::with_picks(PathBuf::from(path), picks)
+            })
+            .collect()
+    }
+
+    /// The time the tests pick at, after every pick made with `picked`.
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 10, 12, 0, 0).unwrap()
+    }
+
+    fn picked(path: &str, day: u32) -> HistoryEntry {
+        HistoryEntry {
+            path: path.to_string(),
+            picked_at: Utc.with_ymd_and_hms(2024, 1, day, 12, 0, 0).unwrap(),
+            rating: None,
+            count: 1,
+        }
+    }
+
+    /// Counts how often each path is chosen over `rounds` selections with a seeded RNG.
+    fn tally(
+        strategy: &mut dyn SelectionStrategy,
+        entries: &[VideoEntry],
+        rounds: usize,
+    ) -> HashMap<PathBuf, usize> {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut counts = HashMap::new();
+        for _ in 0..rounds {
+            let entry = strategy.select(entries, now(), &mut rng).unwrap();
+            *counts.entry(entry.path.clone()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn test_weighted_prefers_less_picked() {
+        let entries = make_entries(&[("a.mp4", 0), ("b.mp4", 9)]);
+        let counts = tally(&mut InverseCountWeighting, &entries, 1000);
+        // Expected ratio is 1.0 : 0.1.
+        assert!(counts[&PathBuf::from("a.mp4")] > 850);
+        assert!(counts[&PathBuf::from("b.mp4")] > 30);
+    }
+
+    #[test]
+    fn test_decayed_forgives_old_picks() {
+        let long_ago = |path: &str| HistoryEntry {
+            path: path.to_string(),
+            picked_at: now() - chrono::Duration::days(3 * 365),
+            rating: None,
+            count: 1,
+        };
+        let history = vec![
+            HistoryEntry {
+                path: "recent.mp4".to_string(),
+                picked_at: now(),
+                rating: None,
+                count: 1,
+            },
+            long_ago("old.mp4"),
+            long_ago("old.mp4"),
+            long_ago("old.mp4"),
+        ];
+        let entries = entries_with_history(&["old.mp4", "recent.mp4"], &history);
+        let mut strategy = StrategyKind::Decayed.build(&StrategyOptions::default());
+        let counts = tally(strategy.as_mut(), &entries, 1000);
+        // Expected ratio is about 1.0 : 0.5, the reverse of plain inverse-count weighting.
+        assert!(counts[&PathBuf::from("old.mp4")] > 600);
+    }
+
+    #[test]
+    fn test_uniform_ignores_pick_counts() {
+        let entries = make_entries(&[("a.mp4", 0), ("b.mp4", 9)]);
+        let counts = tally(&mut UniformRandom, &entries, 1000);
+        assert!(counts[&PathBuf::from("a.mp4")].abs_diff(500) < 75);
+    }
+
+    #[test]
+    fn test_least_recent_prefers_never_then_oldest() {
+        let history = vec![picked("a.mp4", 2), picked("b.mp4", 1)];
+        let entries = entries_with_history(&["a.mp4", "b.mp4", "c.mp4", "d.mp4"], &history);
+        let counts = tally(&mut LeastRecentlyPicked, &entries, 200);
+        assert_eq!(counts.len(), 2); // Only the never-picked c and d.
+        assert!(counts.contains_key(&PathBuf::from("c.mp4")));
+        assert!(counts.contains_key(&PathBuf::from("d.mp4")));
+
+        let entries = entries_with_history(&["a.mp4", "b.mp4"], &history);
+        let counts = tally(&mut LeastRecentlyPicked, &entries, 10);
+        assert_eq!(counts[&PathBuf::from("b.mp4")], 10);
+    }
+
+    #[test]
+    fn test_never_picked_first_then_weighted() {
+        let entries = make_entries(&[("a.mp4", 3), ("b.mp4", 0)]);
+        let counts = tally(&mut NeverPickedFirst, &entries, 50);
+        assert_eq!(counts[&PathBuf::from("b.mp4")], 50);
+
+        let entries = make_entries(&[("a.mp4", 3), ("b.mp4", 1)]);
+        let counts = tally(&mut NeverPickedFirst, &entries, 50);
+        assert_eq!(counts.len(), 2);
+    }
+
+    #[test]
+    fn test_round_robin_continues_after_last_pick() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let select = |history: &[HistoryEntry], rng: &mut StdRng| {
+            let entries = entries_with_history(&["c.mp4", "a.mp4", "b.mp4"], history);
+            RoundRobin
+                .select(&entries, now(), rng)
+                .unwrap()
+                .path
+                .clone()
+        };
+        assert_eq!(select(&[], &mut rng), PathBuf::from("a.mp4"));
+        let history = vec![picked("b.mp4", 3), picked("a.mp4", 2)];
+        assert_eq!(select(&history, &mut rng), PathBuf::from("c.mp4"));
+        let history = vec![picked("c.mp4", 4), picked("b.mp4", 3)];
+        assert_eq!(select(&history, &mut rng), PathBuf::from("a.mp4"));
+    }
+
+    #[test]
+    fn test_same_seed_gives_same_sequence() {
+        let history = vec![picked("a.mp4", 2), picked("b.mp4", 1), picked("a.mp4", 3)];
+        let entries = entries_with_history(&["a.mp4", "b.mp4", "c.mp4", "d.mp4"], &history);
+        let kinds = StrategyKind::value_variants()
+            .iter()
+            .filter(|kind| **kind != StrategyKind::ShuffleBag); // Keeps state on disk.
+        for kind in kinds {
+            let sequence = |seed: u64| {
+                let mut strategy = kind.build(&StrategyOptions::default());
+                let mut rng = StdRng::seed_from_u64(seed);
+                (0..20)
+                    .map(|_| {
+                        let entry = strategy.select(&entries, now(), &mut rng);
+                        entry.unwrap().path.clone()
+                    })
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(sequence(99), sequence(99), "{} is not reproducible", kind);
+        }
+    }
+
+    #[test]
+    fn test_empty_entries() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for kind in StrategyKind::value_variants() {
+            let mut strategy = kind.build(&StrategyOptions::default());
+            assert!(strategy.select(&[], now(), &mut rng).is_none());
+        }
+    }
+
+    #[test]
+    fn test_kind_display_matches_cli_name() {
+        assert_eq!(StrategyKind::LeastRecent.to_string(), "least-recent");
+        assert_eq!(
+            StrategyKind::from_str("never-picked-first", false),
+            Ok(StrategyKind::NeverPickedFirst)
+        );
+    }
+}
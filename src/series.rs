@@ -0,0 +1,304 @@
+// src/series.rs
+
+use crate::selection_strategy::{InverseCountWeighting, SelectionStrategy};
+use crate::video_entry::VideoEntry;
+use chrono::{DateTime, Utc};
+use rand::prelude::*;
+use regex::Regex;
+use std::{collections::BTreeMap, fmt, path::Path, sync::LazyLock};
+
+/// `S01E03`, `s1.e3`, `S01 E03`.
+static SEASON_EPISODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\bS(\d{1,2})[ ._-]?E(\d{1,3})").unwrap());
+/// `1x03`. Resolutions such as `1920x1080` do not match.
+static CROSS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(\d{1,2})x(\d{1,3})\b").unwrap());
+/// `Episode 3`, `Ep. 03`, `ep_3`.
+static EPISODE_ONLY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\bEp(?:isode)?\.?[ ._-]*(\d{1,3})\b").unwrap());
+/// Season folders such as `Season 01`, `Series 2` or `S03`.
+static SEASON_FOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^(?:Season|Series|S)[ ._-]*(\d{1,2})$").unwrap());
+
+/// An episode of a series, as recognized from a file's name and folders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Episode {
+    /// The series name, e.g. `Show`.
+    pub series: String,
+    /// The season number; 1 if neither the name nor a season folder gives one.
+    pub season: u32,
+    /// The episode number within the season.
+    pub number: u32,
+}
+
+impl Episode {
+    /// The key episodes of the same series share: the name, case and spacing ignored.
+    pub fn series_key(&self) -> String {
+        self.series.to_lowercase()
+    }
+}
+
+impl fmt::Display for Episode {
+    /// Writes e.g. `Show S01E03`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} S{:02}E{:02}", self.series, self.season, self.number)
+    }
+}
+
+/// Turns a file name fragment like `The.Show -` into `The Show`.
+fn clean_name(raw: &str) -> String {
+    raw.split(|c: char| c.is_whitespace() || c == '.' || c == '_')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_matches(|c: char| c == '-' || c == '[' || c == '(' || c.is_whitespace())
+        .to_string()
+}
+
+/// Recognizes `path` as an episode if its file name contains `SxxEyy`, `1x03` or
+/// `Episode 3`.
+///
+/// The series name is the part of the file name before the episode number. If that is
+/// empty (e.g. `Episode 3.mkv`), the name of the containing folder is used instead,
+/// skipping a season folder like `Season 01`, which also supplies the season number
+/// when the file name has none.
+///
+/// Returns `None` for files that do not look like episodes.
+pub fn parse_episode(path: &Path) -> Option<Episode> {
+    let stem = path.file_stem()?.to_string_lossy();
+    let (prefix_end, season, number) = if let Some(caps) = SEASON_EPISODE.captures(&stem) {
+        (
+            caps.get(0)?.start(),
+            Some(caps[1].parse().ok()?),
+            caps[2].parse().ok()?,
+        )
+    } else if let Some(caps) = CROSS.captures(&stem) {
+        (
+            caps.get(0)?.start(),
+            Some(caps[1].parse().ok()?),
+            caps[2].parse().ok()?,
+        )
+    } else {
+        let caps = EPISODE_ONLY.captures(&stem)?;
+        (caps.get(0)?.start(), None, caps[1].parse().ok()?)
+    };
+
+    let mut folder = path.parent();
+    let folder_name = |folder: Option<&Path>| {
+        folder
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy().into_owned())
+    };
+    let mut folder_season = None;
+    if let Some(name) = folder_name(folder) {
+        if let Some(caps) = SEASON_FOLDER.captures(&name) {
+            folder_season = caps[1].parse().ok();
+            folder = folder.and_then(Path::parent);
+        }
+    }
+
+    let mut series = clean_name(&stem[..prefix_end]);
+    if series.is_empty() {
+        series = clean_name(&folder_name(folder)?);
+    }
+    if series.is_empty() {
+        return None;
+    }
+    Some(Episode {
+        series,
+        season: season.or(folder_season).unwrap_or(1),
+        number,
+    })
+}
+
+/// Treats each series as one candidate and, when a series comes up, returns its next
+/// episode rather than a random one.
+///
+/// The next episode is the first unwatched episode after the most recently watched one,
+/// in season and episode order. If every later episode has been watched, the first
+/// unwatched episode from the start is taken; once the whole series has been watched,
+/// it continues after the most recent one. Files that are not episodes are candidates of
+/// their own. Candidates are chosen by inverse-count weighting, so a series whose next
+/// episode is unwatched is as likely as an unwatched movie.
+#[derive(Debug, Default)]
+pub struct NextEpisode;
+
+/// Returns the next episode to watch from `episodes`, sorted in watching order.
+fn next_episode<'a>(episodes: &[&'a VideoEntry]) -> Option<&'a VideoEntry> {
+    let latest = episodes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, entry)| entry.last_picked.map(|time| (time, i)))
+        .max()
+        .map(|(_, i)| i);
+    let Some(latest) = latest else {
+        return episodes.first().copied();
+    };
+    let unwatched = |entry: &&&VideoEntry| entry.pick_count == 0;
+    episodes[latest + 1..]
+        .iter()
+        .find(unwatched)
+        .or_else(|| episodes.iter().find(unwatched))
+        .or_else(|| episodes.get((latest + 1) % episodes.len()))
+        .copied()
+}
+
+impl SelectionStrategy for NextEpisode {
+    fn select<'a>(
+        &mut self,
+        entries: &'a [VideoEntry],
+        now: DateTime<Utc>,
+        rng: &mut dyn Rng,
+    ) -> Option<&'a VideoEntry> {
+        let mut series: BTreeMap<String, Vec<(u32, u32, &VideoEntry)>> = BTreeMap::new();
+        let mut candidates: Vec<&VideoEntry> = Vec::new();
+        for entry in entries {
+            match parse_episode(&entry.path) {
+                Some(episode) => series.entry(episode.series_key()).or_default().push((
+                    episode.season,
+                    episode.number,
+                    entry,
+                )),
+                None => candidates.push(entry),
+            }
+        }
+        for mut episodes in series.into_values() {
+            episodes.sort_by(|a, b| (a.0, a.1, &a.2.path).cmp(&(b.0, b.1, &b.2.path)));
+            let ordered: Vec<&VideoEntry> = episodes.iter().map(|(_, _, entry)| *entry).collect();
+            candidates.extend(next_episode(&ordered));
+        }
+
+        let owned: Vec<VideoEntry> = candidates.into_iter().cloned().collect();
+        let chosen = InverseCountWeighting.select(&owned, now, rng)?;
+        entries.iter().find(|entry| entry.path == chosen.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use rand::rngs::StdRng;
+    use std::path::PathBuf;
+
+    fn episode(series: &str, season: u32, number: u32) -> Option<Episode> {
+        Some(Episode {
+            series: series.to_string(),
+            season,
+            number,
+        })
+    }
+
+    #[test]
+    fn test_parse_episode() {
+        let parse = |path: &str| parse_episode(Path::new(path));
+        assert_eq!(
+            parse("/tv/Show/Season 01/Show S01E03.mkv"),
+            episode("Show", 1, 3)
+        );
+        assert_eq!(
+            parse("/tv/The.Show.s02e10.720p.mkv"),
+            episode("The Show", 2, 10)
+        );
+        assert_eq!(parse("/tv/Show - 1x03 - Title.avi"), episode("Show", 1, 3));
+        assert_eq!(
+            parse("/tv/Anime/Season 2/Episode 7.mp4"),
+            episode("Anime", 2, 7)
+        );
+        assert_eq!(parse("/tv/Cartoon/Ep. 12.mp4"), episode("Cartoon", 1, 12));
+        assert_eq!(parse("/movies/Movie (2010) 1920x1080.mkv"), None);
+        assert_eq!(parse("/movies/Heat.mkv"), None);
+        assert_eq!(
+            episode("Show", 1, 3).unwrap().to_string(),
+            "Show S01E03".to_string()
+        );
+    }
+
+    fn entries(picks: &[(&str, Option<u32>)]) -> Vec<VideoEntry> {
+        picks
+            .iter()
+            .map(|(path, day)| {
+                let picked_at = day
+                    .map(|day| Utc.with_ymd_and_hms(2024, 1, day, 20, 0, 0).unwrap())
+                    .into_iter()
+                    .collect();
+                VideoEntry::with_picks(PathBuf::from(path), picked_at)
+            })
+            .collect()
+    }
+
+    fn pick(candidates: &[VideoEntry]) -> PathBuf {
+        let mut rng = StdRng::seed_from_u64(0);
+        NextEpisode
+            .select(candidates, Utc::now(), &mut rng)
+            .unwrap()
+            .path
+            .clone()
+    }
+
+    #[test]
+    fn test_starts_a_new_series_at_the_first_episode() {
+        let candidates = entries(&[
+            ("/tv/Show/Season 02/Show S02E01.mkv", None),
+            ("/tv/Show/Season 01/Show S01E02.mkv", None),
+            ("/tv/Show/Season 01/Show S01E01.mkv", None),
+        ]);
+        assert_eq!(
+            pick(&candidates),
+            PathBuf::from("/tv/Show/Season 01/Show S01E01.mkv")
+        );
+    }
+
+    #[test]
+    fn test_continues_after_the_last_watched_episode() {
+        let candidates = entries(&[
+            ("/tv/Show S01E01.mkv", Some(1)),
+            ("/tv/Show S01E02.mkv", Some(2)),
+            ("/tv/Show S01E03.mkv", None),
+            ("/tv/Show S01E04.mkv", None),
+        ]);
+        assert_eq!(pick(&candidates), PathBuf::from("/tv/Show S01E03.mkv"));
+
+        // Skipping ahead continues from there, then comes back for the skipped ones.
+        let candidates = entries(&[
+            ("/tv/Show S01E01.mkv", Some(1)),
+            ("/tv/Show S01E02.mkv", None),
+            ("/tv/Show S01E03.mkv", Some(2)),
+        ]);
+        assert_eq!(pick(&candidates), PathBuf::from("/tv/Show S01E02.mkv"));
+
+        // A fully watched series is rewatched from after the latest episode.
+        let candidates = entries(&[
+            ("/tv/Show S01E01.mkv", Some(3)),
+            ("/tv/Show S01E02.mkv", Some(1)),
+            ("/tv/Show S01E03.mkv", Some(2)),
+        ]);
+        assert_eq!(pick(&candidates), PathBuf::from("/tv/Show S01E02.mkv"));
+    }
+
+    #[test]
+    fn test_series_count_as_one_candidate() {
+        let names: Vec<String> = (1..=50)
+            .map(|i| format!("/tv/Show S01E{:02}.mkv", i))
+            .collect();
+        let mut picks: Vec<(&str, Option<u32>)> =
+            names.iter().map(|name| (name.as_str(), None)).collect();
+        picks.push(("/movies/Heat.mkv", None));
+        let candidates = entries(&picks);
+        let first_episode = PathBuf::from("/tv/Show S01E01.mkv");
+
+        let mut rng = StdRng::seed_from_u64(4);
+        let movies = (0..1000)
+            .filter(|_| {
+                let entry = NextEpisode.select(&candidates, Utc::now(), &mut rng).unwrap();
+                assert!(entry.path.starts_with("/movies") || entry.path == first_episode);
+                entry.path.starts_with("/movies")
+            })
+            .count();
+        assert!(
+            (400..600).contains(&movies),
+            "movies picked {} times",
+            movies
+        );
+    }
+}
@@ -0,0 +1,156 @@
+// src/settings.rs
+
+use crate::file_utils::get_settings_path;
+use crate::history_store::HistoryBackend;
+use crate::preferences::check_favorite_weight;
+use crate::retention::RetentionPolicy;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, Error as IoError},
+    path::{Path, PathBuf},
+};
+
+/// User settings read from `config.json` in the application data directory.
+/// Every field is optional; missing fields fall back to their defaults, and command
+/// line options take precedence over values from the file.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// File extensions (without the dot) recognized as videos.
+    /// Replaces the built-in list from `config::VIDEO_EXTENSIONS` when set.
+    pub video_extensions: Option<Vec<String>>,
+    /// Also detect videos by their container signature, regardless of extension.
+    pub detect_by_content: bool,
+    /// Treat identical files as one video for selection and history.
+    pub detect_duplicates: bool,
+    /// Folder depth for folder-balanced selection; `config::DEFAULT_FOLDER_DEPTH` if unset.
+    pub folder_depth: Option<usize>,
+    /// Weight multipliers for folder-balanced selection, keyed by folder. An entry applies
+    /// to the folder and its subfolders; 0 excludes them.
+    pub folder_weights: HashMap<PathBuf, f64>,
+    /// Weight multiplier for favorites; `config::DEFAULT_FAVORITE_WEIGHT` if unset.
+    /// Must be above 0.
+    #[serde(deserialize_with = "deserialize_favorite_weight")]
+    pub favorite_weight: Option<f64>,
+    /// Where the pick history is kept: `"json"` (the default) or `"sqlite"`.
+    /// Run `file_picker history import` once before switching to SQLite.
+    pub history_backend: HistoryBackend,
+    /// How much of the pick history to keep, e.g.
+    /// `{ "keep_last": 5000, "max_age": "2years", "collapse": true }`. Keeps everything by default.
+    pub history_retention: RetentionPolicy,
+}
+
+/// Reads `favorite_weight`, rejecting weights `check_favorite_weight` does not accept.
+fn deserialize_favorite_weight<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<f64>, D::Error> {
+    Option::<f64>::deserialize(deserializer)?
+        .map(|weight| check_favorite_weight(weight).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+impl Settings {
+    /// Loads settings from the config file.
+    /// If `custom_path` is provided, it uses that file instead of the default config file.
+    /// If the file doesn't exist, default settings are returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the config file path cannot be determined, the file cannot be
+    /// read, or it is not valid JSON. Unlike the history, a hand-edited config file with a
+    /// typo should be reported rather than silently ignored.
+    pub fn load(custom_path: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let settings_path_buf;
+        let settings_path = match custom_path {
+            Some(p) => p,
+            None => {
+                settings_path_buf = get_settings_path()?;
+                &settings_path_buf
+            }
+        };
+
+        match File::open(settings_path) {
+            Ok(file) => serde_json::from_reader(BufReader::new(file)).map_err(|e| {
+                IoError::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid config file '{}': {}", settings_path.display(), e),
+                )
+                .into()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_load_settings() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(
+            &path,
+            r#"{ "video_extensions": ["ts", "m2ts"], "detect_by_content": true,
+                 "history_backend": "sqlite", "history_retention": { "keep_last": 100 } }"#,
+        )
+        .unwrap();
+
+        let settings = Settings::load(Some(&path)).unwrap();
+        assert_eq!(settings.history_backend, HistoryBackend::Sqlite);
+        assert_eq!(settings.history_retention.keep_last, Some(100));
+        assert_eq!(
+            settings.video_extensions,
+            Some(vec!["ts".to_string(), "m2ts".to_string()])
+        );
+        assert!(settings.detect_by_content);
+    }
+
+    #[test]
+    fn test_load_folder_weights() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(
+            &path,
+            r#"{ "folder_depth": 2, "folder_weights": { "/videos/Movies": 2.5 } }"#,
+        )
+        .unwrap();
+
+        let settings = Settings::load(Some(&path)).unwrap();
+        assert_eq!(settings.folder_depth, Some(2));
+        assert_eq!(
+            settings.folder_weights,
+            HashMap::from([(PathBuf::from("/videos/Movies"), 2.5)])
+        );
+    }
+
+    #[test]
+    fn test_load_settings_missing_and_partial() {
+        let dir = tempdir().unwrap();
+        let missing = dir.path().join("missing.json");
+        assert_eq!(Settings::load(Some(&missing)).unwrap(), Settings::default());
+
+        let partial = dir.path().join("partial.json");
+        std::fs::write(&partial, r#"{ "detect_by_content": true }"#).unwrap();
+        let settings = Settings::load(Some(&partial)).unwrap();
+        assert_eq!(settings.video_extensions, None);
+        assert!(settings.detect_by_content);
+    }
+
+    #[test]
+    fn test_load_settings_invalid() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, r#"{ "video_extensions": "mp4" }"#).unwrap();
+        assert!(Settings::load(Some(&path)).is_err());
+
+        std::fs::write(&path, r#"{ "favorite_weight": 0 }"#).unwrap();
+        let error = Settings::load(Some(&path)).unwrap_err().to_string();
+        assert!(error.contains("invalid favorite weight"), "{}", error);
+    }
+}
@@ -0,0 +1,316 @@
+// src/shuffle_bag.rs
+
+use crate::file_utils::{atomic_write, get_shuffle_bag_path, root_of};
+use crate::selection_strategy::SelectionStrategy;
+use crate::video_entry::VideoEntry;
+use chrono::{DateTime, Utc};
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+};
+
+/// The state of one library root's bag: the videos not yet drawn in the current
+/// cycle, and the ones already drawn.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Bag {
+    /// Videos that can still be drawn before the bag is refilled.
+    pub remaining: BTreeSet<PathBuf>,
+    /// Videos drawn in the current cycle.
+    pub drawn: BTreeSet<PathBuf>,
+}
+
+impl Bag {
+    /// Brings the bag in line with the videos currently under its root: deleted videos
+    /// are dropped, and new ones join the current cycle. Returns true if anything changed.
+    fn reconcile(&mut self, videos: &BTreeSet<&Path>) -> bool {
+        let before = (self.remaining.len(), self.drawn.len());
+        self.remaining
+            .retain(|path| videos.contains(path.as_path()));
+        self.drawn.retain(|path| videos.contains(path.as_path()));
+        let mut changed = before != (self.remaining.len(), self.drawn.len());
+        for video in videos {
+            if !self.remaining.contains(*video) && !self.drawn.contains(*video) {
+                self.remaining.insert(video.to_path_buf());
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Starts a new cycle by putting every drawn video back into the bag.
+    fn refill(&mut self) {
+        self.remaining.append(&mut self.drawn);
+    }
+}
+
+/// The shuffle bags of every library root, persisted in the app data directory so a
+/// cycle survives restarts.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct ShuffleBagStore {
+    /// Bags keyed by library root.
+    bags: BTreeMap<PathBuf, Bag>,
+}
+
+impl ShuffleBagStore {
+    /// Loads the shuffle bags from disk.
+    /// If `custom_path` is provided, it uses that file instead of the default bag file.
+    /// A missing or unparsable file yields empty bags, which start a fresh cycle.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bag file path cannot be determined or if an
+    /// I/O error (other than `NotFound`) occurs while reading the file.
+    pub fn load(custom_path: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let bag_path_buf;
+        let bag_path = match custom_path {
+            Some(p) => p,
+            None => {
+                bag_path_buf = get_shuffle_bag_path()?;
+                &bag_path_buf
+            }
+        };
+
+        match File::open(bag_path) {
+            Ok(file) => match serde_json::from_reader(BufReader::new(file)) {
+                Ok(store) => Ok(store),
+                Err(e) => {
+                    log::warn!(
+                        "Could not parse shuffle bags at '{}' ({}). Starting new cycles.",
+                        bag_path.display(),
+                        e
+                    );
+                    Ok(Self::default())
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// Saves the shuffle bags to disk.
+    /// If `custom_path` is provided, it saves to that file instead of the default bag file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bag file path cannot be determined, or if
+    /// I/O or serialization errors occur during saving. The file is replaced atomically,
+    /// so it keeps its previous contents if saving fails.
+    pub fn save(&self, custom_path: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+        let bag_path_buf;
+        let bag_path = match custom_path {
+            Some(p) => p,
+            None => {
+                bag_path_buf = get_shuffle_bag_path()?;
+                &bag_path_buf
+            }
+        };
+
+        atomic_write(bag_path, |writer| {
+            serde_json::to_writer(writer, self)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
+
+/// Draws every video once, in random order, before any video repeats.
+///
+/// Each library root has its own bag. When none of the candidates are left in the bags
+/// of the current roots, the drawn videos are put back and a new cycle starts. Videos
+/// excluded by filters are never drawn, so they stay in the bag until the filters are lifted.
+#[derive(Debug)]
+pub struct ShuffleBag {
+    store: ShuffleBagStore,
+    roots: Vec<PathBuf>,
+    custom_path: Option<PathBuf>,
+}
+
+impl ShuffleBag {
+    /// Creates a shuffle bag backed by the bag file in the app data directory, or by
+    /// `custom_path` if given. Unreadable bag files are logged and start a fresh cycle.
+    pub fn new(custom_path: Option<PathBuf>) -> Self {
+        let store = ShuffleBagStore::load(custom_path.as_deref()).unwrap_or_else(|e| {
+            log::warn!("Could not load shuffle bags: {}. Starting new cycles.", e);
+            ShuffleBagStore::default()
+        });
+        Self {
+            store,
+            roots: Vec::new(),
+            custom_path,
+        }
+    }
+
+    /// Persists the bags, logging (rather than failing the pick) on error.
+    fn save(&self) {
+        if let Err(e) = self.store.save(self.custom_path.as_deref()) {
+            log::warn!("Failed to save shuffle bags: {}", e);
+        }
+    }
+
+    /// Returns the bag of the root `path` belongs to, creating it if needed.
+    fn bag_for(&mut self, path: &Path) -> Option<&mut Bag> {
+        let root = root_of(path, &self.roots)?.to_path_buf();
+        Some(self.store.bags.entry(root).or_default())
+    }
+
+    /// Returns true if `path` has not been drawn in its root's current cycle.
+    fn is_remaining(&self, path: &Path) -> bool {
+        root_of(path, &self.roots)
+            .and_then(|root| self.store.bags.get(root))
+            .is_some_and(|bag| bag.remaining.contains(path))
+    }
+}
+
+impl SelectionStrategy for ShuffleBag {
+    fn library_changed(&mut self, roots: &[PathBuf], videos: &[PathBuf]) {
+        self.roots = roots.to_vec();
+        let mut per_root: BTreeMap<&Path, BTreeSet<&Path>> = roots
+            .iter()
+            .map(|root| (root.as_path(), BTreeSet::new()))
+            .collect();
+        for video in videos {
+            if let Some(root) = root_of(video, roots) {
+                per_root.entry(root).or_default().insert(video);
+            }
+        }
+
+        let mut changed = false;
+        for (root, videos) in per_root {
+            let bag = self.store.bags.entry(root.to_path_buf()).or_default();
+            changed |= bag.reconcile(&videos);
+        }
+        if changed {
+            self.save();
+        }
+    }
+
+    fn select<'a>(
+        &mut self,
+        entries: &'a [VideoEntry],
+        _now: DateTime<Utc>,
+        rng: &mut dyn Rng,
+    ) -> Option<&'a VideoEntry> {
+        let mut remaining: Vec<&VideoEntry> = entries
+            .iter()
+            .filter(|entry| self.is_remaining(&entry.path))
+            .collect();
+        if remaining.is_empty() && !entries.is_empty() {
+            log::info!("Shuffle bag exhausted for the current candidates; starting a new cycle.");
+            for root in &self.roots {
+                if let Some(bag) = self.store.bags.get_mut(root) {
+                    bag.refill();
+                }
+            }
+            remaining = entries
+                .iter()
+                .filter(|entry| self.is_remaining(&entry.path))
+                .collect();
+        }
+
+        let Some(chosen) = remaining.choose(rng).copied() else {
+            // None of the candidates belong to a bag (e.g. the library was not reported).
+            return entries.choose(rng);
+        };
+        if let Some(bag) = self.bag_for(&chosen.path) {
+            bag.remaining.remove(&chosen.path);
+            bag.drawn.insert(chosen.path.clone());
+        }
+        self.save();
+        Some(chosen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use std::collections::HashSet;
+    use tempfile::tempdir;
+
+    fn entries(paths: &[PathBuf]) -> Vec<VideoEntry> {
+        paths
+            .iter()
+            .map(|path| VideoEntry::new(path.clone(), 0))
+            .collect()
+    }
+
+    #[test]
+    fn test_draws_each_video_once_per_cycle_and_persists() {
+        let dir = tempdir().unwrap();
+        let bag_file = dir.path().join("bags.json");
+        let root = PathBuf::from("/videos");
+        let videos: Vec<PathBuf> = (0..5).map(|i| root.join(format!("{}.mp4", i))).collect();
+        let candidates = entries(&videos);
+        let mut rng = StdRng::seed_from_u64(3);
+
+        let mut bag = ShuffleBag::new(Some(bag_file.clone()));
+        bag.library_changed(std::slice::from_ref(&root), &videos);
+        let mut seen = HashSet::new();
+        for _ in 0..3 {
+            let entry = bag.select(&candidates, Utc::now(), &mut rng).unwrap();
+            assert!(seen.insert(entry.path.clone()));
+        }
+
+        // A new session continues the same cycle.
+        let mut bag = ShuffleBag::new(Some(bag_file));
+        bag.library_changed(std::slice::from_ref(&root), &videos);
+        for _ in 0..2 {
+            let entry = bag.select(&candidates, Utc::now(), &mut rng).unwrap();
+            assert!(seen.insert(entry.path.clone()));
+        }
+        assert_eq!(seen.len(), 5);
+
+        // The bag is empty now, so the next pick starts a new cycle.
+        assert!(bag.select(&candidates, Utc::now(), &mut rng).is_some());
+        assert_eq!(bag.store.bags.get(&root).unwrap().remaining.len(), 4);
+    }
+
+    #[test]
+    fn test_reconciles_additions_and_deletions() {
+        let dir = tempdir().unwrap();
+        let root = PathBuf::from("/videos");
+        let a = root.join("a.mp4");
+        let b = root.join("b.mp4");
+        let c = root.join("c.mp4");
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let mut bag = ShuffleBag::new(Some(dir.path().join("bags.json")));
+        bag.library_changed(std::slice::from_ref(&root), &[a.clone(), b.clone()]);
+        let drawn = bag
+            .select(&entries(&[a.clone(), b.clone()]), Utc::now(), &mut rng)
+            .unwrap()
+            .path
+            .clone();
+
+        // The drawn video is deleted and a new one appears.
+        let kept = if drawn == a { b.clone() } else { a.clone() };
+        bag.library_changed(std::slice::from_ref(&root), &[kept.clone(), c.clone()]);
+        let state = bag.store.bags.get(&root).unwrap();
+        assert_eq!(state.remaining, BTreeSet::from([kept, c]));
+        assert!(state.drawn.is_empty());
+    }
+
+    #[test]
+    fn test_filtered_candidates_do_not_consume_the_cycle() {
+        let dir = tempdir().unwrap();
+        let root = PathBuf::from("/videos");
+        let a = root.join("a.mp4");
+        let b = root.join("b.mp4");
+        let mut rng = StdRng::seed_from_u64(5);
+
+        let mut bag = ShuffleBag::new(Some(dir.path().join("bags.json")));
+        bag.library_changed(std::slice::from_ref(&root), &[a.clone(), b.clone()]);
+        // Only `a` passes the filters: it is drawn, and drawn again in a new cycle.
+        let candidates = entries(std::slice::from_ref(&a));
+        for _ in 0..2 {
+            let entry = bag.select(&candidates, Utc::now(), &mut rng).unwrap();
+            assert_eq!(entry.path, a);
+        }
+        // `b` was never drawn, so it is still in the bag.
+        assert!(bag.store.bags.get(&root).unwrap().remaining.contains(&b));
+    }
+}
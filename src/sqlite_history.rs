@@ -0,0 +1,423 @@
+// src/sqlite_history.rs
+
+use crate::history_manager::HistoryEntry;
+use crate::history_store::HistoryStore;
+use crate::retention::{PrunePlan, RetentionPolicy};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, types::Type, Connection, OptionalExtension, Row, TransactionBehavior};
+use std::{collections::HashMap, path::Path, time::Duration};
+
+/// How long to wait for another picker instance to finish writing before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One row per pick, or per group of picks collapsed by the retention policy (`count`).
+/// Times are stored as fixed-width RFC 3339 text in UTC, so they sort chronologically as
+/// strings and stay readable in any SQLite browser.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS picks (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL,
+        picked_at TEXT NOT NULL,
+        rating INTEGER,
+        count INTEGER NOT NULL DEFAULT 1
+    );
+    CREATE INDEX IF NOT EXISTS picks_by_path ON picks (path, picked_at);
+    CREATE INDEX IF NOT EXISTS picks_by_time ON picks (picked_at);
+";
+
+/// The pick history in an SQLite database. Only the picks a query asks for are read, so
+/// its cost does not grow with the length of the history.
+#[derive(Debug)]
+pub struct SqliteHistoryStore {
+    connection: Connection,
+}
+
+/// Formats a pick time the way it is stored, e.g. `2024-01-01T20:00:00.000000000Z`.
+fn to_stored_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+/// Reads a `path, picked_at, rating, count` row.
+fn entry_from_row(row: &Row) -> rusqlite::Result<HistoryEntry> {
+    let picked_at: String = row.get(1)?;
+    let picked_at = DateTime::parse_from_rfc3339(&picked_at)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(1, Type::Text, Box::new(e)))?
+        .with_timezone(&Utc);
+    Ok(HistoryEntry {
+        path: row.get(0)?,
+        picked_at,
+        rating: row.get(2)?,
+        count: row.get::<_, i64>(3)? as usize,
+    })
+}
+
+/// Returns the picks outside `policy` at `now`, newest first.
+fn expired_entries(
+    connection: &Connection,
+    policy: &RetentionPolicy,
+    now: DateTime<Utc>,
+) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+    // Without a limit, skip every row and accept no time (all stored times sort after "")
+    let keep_last = policy
+        .keep_last
+        .map_or(i64::MAX, |count| i64::try_from(count).unwrap_or(i64::MAX));
+    let cutoff = policy.cutoff(now).map(to_stored_time).unwrap_or_default();
+    let mut statement = connection.prepare_cached(
+        "SELECT path, picked_at, rating, count FROM picks
+         WHERE id IN (SELECT id FROM picks ORDER BY picked_at DESC, id DESC LIMIT -1 OFFSET ?1)
+            OR picked_at < ?2
+         ORDER BY picked_at DESC, id DESC",
+    )?;
+    let entries = statement
+        .query_map(params![keep_last, cutoff], entry_from_row)?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(entries)
+}
+
+impl SqliteHistoryStore {
+    /// Opens the database at `path`, creating it and its tables if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or is not an SQLite database.
+    pub fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let connection = Connection::open(path)?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        connection.execute_batch(SCHEMA)?;
+        // Databases created before retention policies lack the `count` column
+        let has_count: bool = connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM pragma_table_info('picks') WHERE name = 'count')",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_count {
+            connection
+                .execute_batch("ALTER TABLE picks ADD COLUMN count INTEGER NOT NULL DEFAULT 1")?;
+        }
+        Ok(Self { connection })
+    }
+
+    /// Adds `entries` to the database in one transaction, skipping picks it already has
+    /// (same path and time), so importing the same history twice is harmless.
+    /// Returns how many entries were added.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entries cannot be written; nothing is added then.
+    pub fn import(
+        &mut self,
+        entries: &[HistoryEntry],
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let transaction = self.connection.transaction()?;
+        let mut imported = 0;
+        {
+            let mut insert = transaction.prepare(
+                "INSERT INTO picks (path, picked_at, rating, count)
+                 SELECT ?1, ?2, ?3, ?4
+                 WHERE NOT EXISTS (SELECT 1 FROM picks WHERE path = ?1 AND picked_at = ?2)",
+            )?;
+            for entry in entries {
+                imported += insert.execute(params![
+                    entry.path,
+                    to_stored_time(entry.picked_at),
+                    entry.rating,
+                    entry.count as i64
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(imported)
+    }
+
+    /// Runs a query returning `path, picked_at, rating, count` rows.
+    fn query_entries(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+        let mut statement = self.connection.prepare_cached(sql)?;
+        let entries = statement
+            .query_map(params, entry_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(entries)
+    }
+}
+
+impl HistoryStore for SqliteHistoryStore {
+    fn load(&self) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+        self.query_entries(
+            "SELECT path, picked_at, rating, count FROM picks ORDER BY picked_at DESC, id DESC",
+            [],
+        )
+    }
+
+    fn append(&mut self, file_path: &Path) -> Result<HistoryEntry, Box<dyn std::error::Error>> {
+        let entry = HistoryEntry {
+            path: file_path.to_string_lossy().into_owned(),
+            picked_at: Utc::now(),
+            rating: None,
+            count: 1,
+        };
+        self.connection.execute(
+            "INSERT INTO picks (path, picked_at) VALUES (?1, ?2)",
+            params![entry.path, to_stored_time(entry.picked_at)],
+        )?;
+        Ok(entry)
+    }
+
+    fn rate_latest_pick(
+        &mut self,
+        file_path: &Path,
+        rating: u8,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let latest: Option<i64> = self
+            .connection
+            .query_row(
+                "SELECT id FROM picks WHERE path = ?1 ORDER BY picked_at DESC, id DESC LIMIT 1",
+                params![file_path.to_string_lossy()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(id) = latest else {
+            return Ok(false);
+        };
+        self.connection.execute(
+            "UPDATE picks SET rating = ?1 WHERE id = ?2",
+            params![rating.clamp(1, 5), id],
+        )?;
+        Ok(true)
+    }
+
+    fn pick_counts(&self) -> Result<HashMap<String, usize>, Box<dyn std::error::Error>> {
+        let mut statement = self
+            .connection
+            .prepare_cached("SELECT path, SUM(count) FROM picks GROUP BY path")?;
+        let counts = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as usize)))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(counts)
+    }
+
+    fn recent_picks(&self, limit: usize) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+        self.query_entries(
+            "SELECT path, picked_at, rating, count FROM picks
+             ORDER BY picked_at DESC, id DESC LIMIT ?1",
+            params![i64::try_from(limit).unwrap_or(i64::MAX)],
+        )
+    }
+
+    fn picks_by_path(
+        &self,
+        paths: &[&Path],
+    ) -> Result<HashMap<String, Vec<HistoryEntry>>, Box<dyn std::error::Error>> {
+        // One join against a temporary table of the wanted paths, rather than a query per
+        // path: a large library asks for tens of thousands of paths on every pick.
+        let transaction = self.connection.unchecked_transaction()?;
+        transaction.execute_batch(
+            "CREATE TEMP TABLE IF NOT EXISTS wanted_paths (path TEXT PRIMARY KEY);
+             DELETE FROM temp.wanted_paths;",
+        )?;
+        let mut picks: HashMap<String, Vec<HistoryEntry>> = HashMap::new();
+        {
+            let mut insert = transaction
+                .prepare_cached("INSERT OR IGNORE INTO temp.wanted_paths (path) VALUES (?1)")?;
+            for path in paths {
+                insert.execute(params![path.to_string_lossy()])?;
+            }
+            let mut select = transaction.prepare_cached(
+                "SELECT picks.path, picked_at, rating, count
+                 FROM temp.wanted_paths JOIN picks ON picks.path = wanted_paths.path
+                 ORDER BY picks.path, picked_at DESC, id DESC",
+            )?;
+            for entry in select.query_map([], entry_from_row)? {
+                let entry = entry?;
+                picks.entry(entry.path.clone()).or_default().push(entry);
+            }
+        }
+        transaction.execute_batch("DELETE FROM temp.wanted_paths")?;
+        transaction.commit()?;
+        Ok(picks)
+    }
+
+    fn picks_between(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<HistoryEntry>, Box<dyn std::error::Error>> {
+        self.query_entries(
+            "SELECT path, picked_at, rating, count FROM picks
+             WHERE picked_at >= ?1 AND picked_at < ?2
+             ORDER BY picked_at DESC, id DESC",
+            params![to_stored_time(from), to_stored_time(to)],
+        )
+    }
+
+    fn plan_prune(
+        &self,
+        policy: &RetentionPolicy,
+        now: DateTime<Utc>,
+    ) -> Result<PrunePlan, Box<dyn std::error::Error>> {
+        if policy.is_empty() {
+            return Ok(PrunePlan::default());
+        }
+        Ok(policy.plan(&expired_entries(&self.connection, policy, now)?))
+    }
+
+    fn prune(
+        &mut self,
+        policy: &RetentionPolicy,
+        now: DateTime<Utc>,
+    ) -> Result<PrunePlan, Box<dyn std::error::Error>> {
+        if policy.is_empty() {
+            return Ok(PrunePlan::default());
+        }
+        // Planned and applied in one write transaction, so picks by other instances wait
+        let transaction = self
+            .connection
+            .transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let plan = policy.plan(&expired_entries(&transaction, policy, now)?);
+        {
+            let mut delete =
+                transaction.prepare("DELETE FROM picks WHERE path = ?1 AND picked_at = ?2")?;
+            for entry in &plan.removed {
+                delete.execute(params![entry.path, to_stored_time(entry.picked_at)])?;
+            }
+            let mut insert = transaction.prepare(
+                "INSERT INTO picks (path, picked_at, rating, count) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for entry in &plan.collapsed {
+                insert.execute(params![
+                    entry.path,
+                    to_stored_time(entry.picked_at),
+                    entry.rating,
+                    entry.count as i64
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    fn entry(path: &str, day: u32, rating: Option<u8>) -> HistoryEntry {
+        HistoryEntry {
+            path: path.to_string(),
+            picked_at: Utc.with_ymd_and_hms(2024, 1, day, 20, 0, 0).unwrap(),
+            rating,
+            count: 1,
+        }
+    }
+
+    #[test]
+    fn test_import_and_query() {
+        let dir = tempdir().unwrap();
+        let mut store = SqliteHistoryStore::open(&dir.path().join("history.sqlite3")).unwrap();
+        let history = vec![
+            entry("/a.mp4", 3, None),
+            entry("/b.mp4", 2, Some(4)),
+            entry("/a.mp4", 1, None),
+        ];
+        assert_eq!(store.import(&history).unwrap(), 3);
+        assert_eq!(store.import(&history).unwrap(), 0);
+        assert_eq!(store.load().unwrap(), history);
+
+        assert_eq!(
+            store.pick_counts().unwrap(),
+            HashMap::from([("/a.mp4".to_string(), 2), ("/b.mp4".to_string(), 1)])
+        );
+        let picks = store
+            .picks_by_path(&[Path::new("/a.mp4"), Path::new("/c.mp4")])
+            .unwrap();
+        assert_eq!(picks["/a.mp4"], [history[0].clone(), history[2].clone()]);
+        assert!(!picks.contains_key("/c.mp4"));
+        // Asking again (with a path twice) gives the same answer, not left-over paths.
+        let picks = store
+            .picks_by_path(&[Path::new("/b.mp4"), Path::new("/b.mp4")])
+            .unwrap();
+        assert_eq!(picks.len(), 1);
+        assert_eq!(picks["/b.mp4"], history[1..2]);
+        assert_eq!(
+            store
+                .picks_between(history[2].picked_at, history[0].picked_at)
+                .unwrap(),
+            history[1..]
+        );
+        assert_eq!(store.recent_picks(2).unwrap(), history[..2]);
+        assert_eq!(store.recent_picks(10).unwrap(), history);
+    }
+
+    #[test]
+    fn test_append_and_rate() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("history.sqlite3");
+        let mut store = SqliteHistoryStore::open(&file).unwrap();
+        assert!(!store.rate_latest_pick(Path::new("/a.mp4"), 3).unwrap());
+        let first = store.append(Path::new("/a.mp4")).unwrap();
+        let second = store.append(Path::new("/a.mp4")).unwrap();
+        assert!(store.rate_latest_pick(Path::new("/a.mp4"), 7).unwrap());
+
+        // Reopening reads back exactly what was stored, nanoseconds included.
+        let store = SqliteHistoryStore::open(&file).unwrap();
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].picked_at, second.picked_at);
+        assert_eq!(loaded[0].rating, Some(5));
+        assert_eq!(loaded[1], first);
+    }
+
+    #[test]
+    fn test_prune() {
+        let dir = tempdir().unwrap();
+        let mut store = SqliteHistoryStore::open(&dir.path().join("history.sqlite3")).unwrap();
+        let history = vec![
+            entry("/a.mp4", 9, None),
+            entry("/b.mp4", 4, None),
+            entry("/a.mp4", 3, None),
+            entry("/a.mp4", 2, Some(4)),
+            entry("/b.mp4", 1, None),
+        ];
+        store.import(&history).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 1, 10, 0, 0, 0).unwrap();
+        let keep_last = RetentionPolicy {
+            keep_last: Some(2),
+            ..RetentionPolicy::default()
+        };
+        let max_age = RetentionPolicy {
+            max_age: Some(Duration::from_secs(7 * 86_400)),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            store.plan_prune(&keep_last, now).unwrap().removed,
+            history[2..]
+        );
+        assert_eq!(
+            store.plan_prune(&max_age, now).unwrap().removed,
+            history[3..]
+        );
+        assert!(store
+            .plan_prune(&RetentionPolicy::default(), now)
+            .unwrap()
+            .is_empty());
+        assert_eq!(store.load().unwrap(), history);
+
+        let collapse = RetentionPolicy {
+            collapse: true,
+            ..keep_last
+        };
+        let plan = store.prune(&collapse, now).unwrap();
+        assert_eq!(plan.removed, history[2..4]);
+        let mut expected = history.clone();
+        plan.apply_to(&mut expected);
+        assert_eq!(store.load().unwrap(), expected);
+        assert_eq!(expected[2].count, 2);
+        assert_eq!(expected[2].rating, Some(4));
+        assert_eq!(store.pick_counts().unwrap()["/a.mp4"], 3);
+        assert!(store.prune(&collapse, now).unwrap().is_empty());
+    }
+}
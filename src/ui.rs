@@ -1,12 +1,369 @@
 // src/ui.rs
 
-use crate::history_manager::HistoryEntry;
+use crate::candidate_filter::{format_size, parse_duration, parse_size, CandidateFilter};
+use crate::duplicate_finder::DuplicateGroup;
+use crate::file_utils::{ScanProgress, ScanReport};
+use crate::history_manager::{latest_ratings, HistoryEntry};
+use crate::playlist::{Playlist, PlaylistTarget};
+use crate::retention::PrunePlan;
+use crate::selection_strategy::StrategyKind;
 use chrono::{DateTime, Local}; // Use Local timezone for display purposes.
+use clap::ValueEnum;
 use dialoguer::{theme::ColorfulTheme, Input, Select};
-use std::path::PathBuf;
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+/// The maximum number of skipped entries listed individually in a scan summary.
+const MAX_SKIPPED_SHOWN: usize = 10;
+/// How often the scan progress line is redrawn.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Runs `scan` while a background thread keeps a progress line on stderr up to date
+/// (directories visited, videos found), so long scans don't look frozen.
+/// Nothing is drawn for scans that finish within one interval, or if stderr is not a terminal.
+///
+/// # Arguments
+///
+/// * `scan` - The scan to run, given the counters to update.
+pub fn with_scan_progress<T>(scan: impl FnOnce(&ScanProgress) -> T) -> T {
+    let progress = ScanProgress::default();
+    if !io::stderr().is_terminal() {
+        return scan(&progress);
+    }
+
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        let reporter = s.spawn(|| {
+            let mut drawn = false;
+            loop {
+                thread::park_timeout(PROGRESS_INTERVAL);
+                if done.load(Ordering::Relaxed) {
+                    break;
+                }
+                eprint!(
+                    "\r  {} directories visited, {} videos found...",
+                    progress.directories_visited(),
+                    progress.videos_found()
+                );
+                let _ = io::stderr().flush();
+                drawn = true;
+            }
+            if drawn {
+                eprint!("\r\x1b[2K"); // Clear the progress line.
+            }
+        });
+        let result = scan(&progress);
+        done.store(true, Ordering::Relaxed);
+        reporter.thread().unpark();
+        result
+    })
+}
+
+/// Prints a summary of the entries a scan had to skip, if any.
+/// Lists up to `MAX_SKIPPED_SHOWN` paths with their reasons; the rest are only counted.
+///
+/// # Arguments
+///
+/// * `report` - The report returned by the scan.
+pub fn show_scan_summary(report: &ScanReport) {
+    if report.skipped.is_empty() {
+        return;
+    }
+
+    println!(
+        "\n⚠ Found {} videos, but skipped {} unreadable entries:",
+        report.videos.len(),
+        report.skipped.len()
+    );
+    for skipped in report.skipped.iter().take(MAX_SKIPPED_SHOWN) {
+        println!("  - {}: {}", skipped.path.display(), skipped.reason);
+    }
+    if report.skipped.len() > MAX_SKIPPED_SHOWN {
+        println!(
+            "  ...and {} more (run with RUST_LOG=warn to see all).",
+            report.skipped.len() - MAX_SKIPPED_SHOWN
+        );
+    }
+}
+
+/// Prints each group of identical files, followed by the space taken up by the extra copies.
+///
+/// # Arguments
+///
+/// * `groups` - The duplicate groups found in the library.
+pub fn show_duplicate_groups(groups: &[DuplicateGroup]) {
+    if groups.is_empty() {
+        println!("No duplicate videos found.");
+        return;
+    }
+
+    let mut reclaimable = 0;
+    for (i, group) in groups.iter().enumerate() {
+        println!(
+            "\nGroup {} ({} copies, {} each):",
+            i + 1,
+            group.paths.len(),
+            format_size(group.size)
+        );
+        for path in &group.paths {
+            println!("  - {}", path.display());
+        }
+        reclaimable += group.size * (group.paths.len() as u64 - 1);
+    }
+    println!(
+        "\nFound {} duplicate groups; removing the extra copies would free {}.",
+        groups.len(),
+        format_size(reclaimable)
+    );
+}
+
+/// Prints the picks a retention policy removes from the history, per video, with how many
+/// picks they stand for and when they were made.
+///
+/// # Arguments
+///
+/// * `plan` - The entries removed and, with `collapse`, the entries replacing them.
+/// * `dry_run` - True if the plan was only computed, not applied.
+pub fn show_prune_plan(plan: &PrunePlan, dry_run: bool) {
+    if plan.is_empty() {
+        println!("Nothing to prune: the whole history is within the retention policy.");
+        return;
+    }
+
+    let mut by_path: BTreeMap<&str, Vec<&HistoryEntry>> = BTreeMap::new();
+    for entry in &plan.removed {
+        by_path.entry(entry.path.as_str()).or_default().push(entry);
+    }
+    let collapsed: HashSet<&str> = plan.collapsed.iter().map(|entry| entry.path.as_str()).collect();
+    let format_day = |entry: &HistoryEntry| {
+        DateTime::<Local>::from(entry.picked_at).format("%Y-%m-%d").to_string()
+    };
+    for (path, entries) in &by_path {
+        // The removed entries are sorted newest first.
+        let (oldest, newest) = (format_day(entries[entries.len() - 1]), format_day(entries[0]));
+        let dates = if oldest == newest { newest } else { format!("{} to {}", oldest, newest) };
+        let picks: usize = entries.iter().map(|entry| entry.count).sum();
+        println!(
+            "  - {} ({} {}, {}){}",
+            path,
+            picks,
+            if picks == 1 { "pick" } else { "picks" },
+            dates,
+            if collapsed.contains(path) { ", collapsed into one entry" } else { "" }
+        );
+    }
+    println!(
+        "\n{} {} history entries of {} videos{}.",
+        if dry_run { "Would remove" } else { "Removed" },
+        plan.removed.len(),
+        by_path.len(),
+        if collapsed.is_empty() {
+            String::new()
+        } else {
+            format!(", keeping the pick counts of {} of them", collapsed.len())
+        }
+    );
+}
+
+/// Prints a playlist as a numbered list, with each video's length and rating, followed
+/// by the total length.
+///
+/// # Arguments
+///
+/// * `playlist` - The drawn playlist.
+/// * `target` - What was asked for, shown when the playlist falls short of it.
+pub fn show_playlist(playlist: &Playlist, target: PlaylistTarget) {
+    let format_secs = |secs: f64| humantime::format_duration(Duration::from_secs(secs as u64));
+    println!("\n🎬 Playlist ({} videos):", playlist.len());
+    for (i, item) in playlist.items.iter().enumerate() {
+        let length = item
+            .duration_secs
+            .map_or_else(|| "length unknown".to_string(), |secs| format_secs(secs).to_string());
+        let rating = item
+            .entry
+            .rating
+            .map(|rating| format!(" {}", format_rating(rating)))
+            .unwrap_or_default();
+        println!("  {}. {} ({}){}", i + 1, item.entry.path.display(), length, rating);
+    }
+    println!("Total length: {}", format_secs(playlist.total_secs()));
+    if !target.is_reached(playlist.len(), playlist.total_secs()) {
+        println!("Ran out of candidates before reaching the target ({}).", target);
+    }
+}
+
+/// Prompts for a new value of one filter bound. An empty answer clears the bound;
+/// invalid input is reported and asked for again.
+fn prompt_bound<T>(
+    theme: &ColorfulTheme,
+    prompt: &str,
+    current: Option<String>,
+    parse: fn(&str) -> Result<T, String>,
+) -> Result<Option<T>, Box<dyn std::error::Error>> {
+    loop {
+        let input = Input::<String>::with_theme(theme)
+            .with_prompt(format!("{} (empty to clear)", prompt))
+            .with_initial_text(current.clone().unwrap_or_default())
+            .allow_empty(true)
+            .interact_text()?;
+        if input.trim().is_empty() {
+            return Ok(None);
+        }
+        match parse(&input) {
+            Ok(value) => return Ok(Some(value)),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+/// Lets the user change the candidate filters interactively until they choose "Done".
+///
+/// # Arguments
+///
+/// * `filter` - The filters to edit in place.
+/// * `theme` - The `dialoguer::theme::ColorfulTheme` to use for prompts.
+///
+/// # Errors
+///
+/// Returns an error if any dialoguer interaction fails.
+pub fn edit_candidate_filter(
+    filter: &mut CandidateFilter,
+    theme: &ColorfulTheme,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let show_size = |size: Option<u64>| size.map_or_else(|| "any".to_string(), format_size);
+    let show_duration = |duration: Option<std::time::Duration>| {
+        duration.map_or_else(
+            || "any".to_string(),
+            |d| humantime::format_duration(d).to_string(),
+        )
+    };
+    let edit_size = |size: Option<u64>| size.map(|bytes| bytes.to_string());
+    let edit_duration = |duration: Option<std::time::Duration>| {
+        duration.map(|d| humantime::format_duration(d).to_string())
+    };
+
+    loop {
+        let items = [
+            format!("Minimum size: {}", show_size(filter.min_size)),
+            format!("Maximum size: {}", show_size(filter.max_size)),
+            format!("Modified within: {}", show_duration(filter.modified_within)),
+            format!("Minimum duration: {}", show_duration(filter.min_duration)),
+            format!("Maximum duration: {}", show_duration(filter.max_duration)),
+            "Clear all filters".to_string(),
+            "Done".to_string(),
+        ];
+        let choice = Select::with_theme(theme)
+            .with_prompt(format!("Candidate filters (active: {})", filter))
+            .items(&items)
+            .default(items.len() - 1)
+            .interact_opt()?
+            .unwrap_or(items.len() - 1); // Esc means "Done".
+
+        match choice {
+            0 => {
+                filter.min_size = prompt_bound(
+                    theme,
+                    "Minimum size, e.g. 700M",
+                    edit_size(filter.min_size),
+                    parse_size,
+                )?
+            }
+            1 => {
+                filter.max_size = prompt_bound(
+                    theme,
+                    "Maximum size, e.g. 4G",
+                    edit_size(filter.max_size),
+                    parse_size,
+                )?
+            }
+            2 => {
+                filter.modified_within = prompt_bound(
+                    theme,
+                    "Modified within, e.g. 30d",
+                    edit_duration(filter.modified_within),
+                    parse_duration,
+                )?
+            }
+            3 => {
+                filter.min_duration = prompt_bound(
+                    theme,
+                    "Minimum duration, e.g. 20m",
+                    edit_duration(filter.min_duration),
+                    parse_duration,
+                )?
+            }
+            4 => {
+                filter.max_duration = prompt_bound(
+                    theme,
+                    "Maximum duration, e.g. 1h30m",
+                    edit_duration(filter.max_duration),
+                    parse_duration,
+                )?
+            }
+            5 => *filter = CandidateFilter::default(),
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// Lets the user pick a different selection strategy.
+/// Returns the chosen strategy, or `current` if the prompt is cancelled.
+///
+/// # Errors
+///
+/// Returns an error if the dialoguer interaction fails.
+pub fn choose_strategy(
+    current: StrategyKind,
+    theme: &ColorfulTheme,
+) -> Result<StrategyKind, Box<dyn std::error::Error>> {
+    let kinds = StrategyKind::value_variants();
+    let items: Vec<String> = kinds
+        .iter()
+        .map(|kind| format!("{} [{}]", kind.description(), kind))
+        .collect();
+    let selection = Select::with_theme(theme)
+        .with_prompt("How should the next video be chosen?")
+        .items(&items)
+        .default(kinds.iter().position(|kind| *kind == current).unwrap_or(0))
+        .interact_opt()?;
+    Ok(selection.map_or(current, |index| kinds[index]))
+}
+
+/// Formats a 1–5 star rating as stars, e.g. `★★★★☆`.
+pub fn format_rating(rating: u8) -> String {
+    let stars = usize::from(rating.min(5));
+    format!("{}{}", "★".repeat(stars), "☆".repeat(5 - stars))
+}
+
+/// Asks for a 1–5 star rating, starting at `current` if the video is already rated.
+/// Returns `None` if the prompt is cancelled.
+///
+/// # Errors
+///
+/// Returns an error if the dialoguer interaction fails.
+pub fn prompt_rating(
+    current: Option<u8>,
+    theme: &ColorfulTheme,
+) -> Result<Option<u8>, Box<dyn std::error::Error>> {
+    let ratings: Vec<u8> = (1..=5).rev().collect();
+    let items: Vec<String> = ratings.iter().map(|rating| format_rating(*rating)).collect();
+    let selection = Select::with_theme(theme)
+        .with_prompt("How would you rate this video?")
+        .items(&items)
+        .default(current.and_then(|c| ratings.iter().position(|r| *r == c)).unwrap_or(2))
+        .interact_opt()?;
+    Ok(selection.map(|index| ratings[index]))
+}
 
 /// Displays recent video history entries in an interactive list.
-/// Shows up to the 20 most recent entries.
+/// Shows up to the 20 most recent entries, with each video's current rating.
+/// If any video is rated, the list can first be narrowed down to one rating.
 /// Allows the user to select an entry to view its full path and timestamp.
 ///
 /// # Arguments
@@ -30,20 +387,68 @@ pub fn view_history(
         return Ok(());
     }
 
-    // Prepare items for the selection list, limiting to the 20 most recent.
-    let items: Vec<String> = history
+    // Optionally narrow the list down to videos with a given rating (`Some(None)`: unrated).
+    let ratings = latest_ratings(history);
+    let mut rating_filter: Option<Option<u8>> = None;
+    if !ratings.is_empty() {
+        let choices: Vec<Option<Option<u8>>> = std::iter::once(None)
+            .chain((1..=5).rev().map(|rating| Some(Some(rating))))
+            .chain(std::iter::once(Some(None)))
+            .collect();
+        let labels: Vec<String> = choices
+            .iter()
+            .map(|choice| match choice {
+                None => "All videos".to_string(),
+                Some(Some(rating)) => format_rating(*rating),
+                Some(None) => "Unrated".to_string(),
+            })
+            .collect();
+        match Select::with_theme(theme)
+            .with_prompt("Show which videos?")
+            .items(&labels)
+            .default(0)
+            .interact_opt()?
+        {
+            Some(index) => rating_filter = choices[index],
+            None => return Ok(()),
+        }
+    }
+    let shown: Vec<&HistoryEntry> = history
         .iter()
+        .filter(|entry| {
+            rating_filter.is_none_or(|wanted| ratings.get(entry.path.as_str()).copied() == wanted)
+        })
         .take(20)
+        .collect();
+    if shown.is_empty() {
+        println!("\n--- No videos with that rating ---");
+        return Ok(());
+    }
+
+    // Prepare items for the selection list, limiting to the 20 most recent.
+    let items: Vec<String> = shown
+        .iter()
         .map(|entry| {
             let local_time: DateTime<Local> = DateTime::from(entry.picked_at); // Convert UTC to local time for display.
             let file_name = PathBuf::from(&entry.path)
                 .file_name()
                 .map(|name| name.to_string_lossy().into_owned())
                 .unwrap_or_else(|| entry.path.clone()); // Fallback to full path if filename cannot be extracted.
+            let rating_note = ratings
+                .get(entry.path.as_str())
+                .map(|rating| format!(" {}", format_rating(*rating)))
+                .unwrap_or_default();
+            // Entries collapsed by the retention policy stand for several picks.
+            let picks_note = match entry.count {
+                1 => "picked on".to_string(),
+                count => format!("picked {} times, last on", count),
+            };
             format!(
-                "{} (picked on {})",
+                "{} ({} {}){}",
                 file_name,
-                local_time.format("%Y-%m-%d %H:%M") // User-friendly date/time format.
+                picks_note,
+                local_time.format("%Y-%m-%d %H:%M"), // User-friendly date/time format.
+                rating_note
             )
         })
         .collect();
@@ -60,7 +465,7 @@ pub fn view_history(
         .interact_opt()?; // Returns Option<usize>; None if Esc is pressed.
 
     if let Some(index) = selection {
-        if let Some(selected_entry) = history.get(index) {
+        if let Some(selected_entry) = shown.get(index) {
             // Optional: Clear screen again before showing details.
             // if let Err(e) = dialoguer::console::Term::stdout().clear_screen() {
             //      eprintln!("Note: Failed to clear screen before showing details: {}", e);
@@ -72,6 +477,9 @@ pub fn view_history(
                 "Picked at: {}",
                 DateTime::<Local>::from(selected_entry.picked_at).format("%Y-%m-%d %H:%M:%S %Z")
             );
+            if let Some(rating) = ratings.get(selected_entry.path.as_str()) {
+                println!("Rating: {}", format_rating(*rating));
+            }
             println!("------------------------------");
 
             Input::<String>::with_theme(theme)
@@ -1,15 +1,29 @@
 // src/video_entry.rs
 
-use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use std::{path::PathBuf, time::Duration};
+
+/// The weight factor for each star rating, from 1 to 5 stars. 3 stars is neutral;
+/// a 1-star video comes up a tenth as often, a 5-star one two and a half times as often.
+const RATING_FACTORS: [f64; 5] = [0.1, 0.5, 1.0, 1.5, 2.5];
 
 /// Represents a video file discovered during scanning.
-/// Includes its path and how many times it has been picked, used for weighting selection.
+/// Includes its path and how many times (and when) it has been picked, used for weighting
+/// selection.
 #[derive(Debug, Clone)]
 pub struct VideoEntry {
     /// The full path to the video file.
     pub path: PathBuf,
     /// The number of times this video has been recorded in the history.
     pub pick_count: usize,
+    /// When this video was last picked, if ever.
+    pub last_picked: Option<DateTime<Utc>>,
+    /// When each recorded pick happened, newest first. Used for recency-decayed weighting.
+    pub picked_at: Vec<DateTime<Utc>>,
+    /// Factor the selection weight is multiplied by, e.g. for favorites. 1.0 by default.
+    pub weight_multiplier: f64,
+    /// The video's current 1–5 star rating, if it has been rated.
+    pub rating: Option<u8>,
 }
 
 impl VideoEntry {
@@ -20,12 +34,40 @@ impl VideoEntry {
     /// * `path` - The `PathBuf` for the video file.
     /// * `pick_count` - How many times this video has been picked previously.
     pub fn new(path: PathBuf, pick_count: usize) -> Self {
-        VideoEntry { path, pick_count }
+        VideoEntry {
+            path,
+            pick_count,
+            last_picked: None,
+            picked_at: Vec::new(),
+            weight_multiplier: 1.0,
+            rating: None,
+        }
+    }
+
+    /// Creates a new `VideoEntry` from the times it was picked.
+    /// The pick count and last-picked time are derived from `picked_at`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The `PathBuf` for the video file.
+    /// * `picked_at` - When the video was picked, in any order.
+    pub fn with_picks(path: PathBuf, mut picked_at: Vec<DateTime<Utc>>) -> Self {
+        picked_at.sort_by(|a, b| b.cmp(a)); // Newest first.
+        let mut entry = Self::new(path, picked_at.len());
+        entry.last_picked = picked_at.first().copied();
+        entry.picked_at = picked_at;
+        entry
+    }
+
+    /// Returns the factor the rating contributes to the weight (1.0 if unrated).
+    pub fn rating_factor(&self) -> f64 {
+        self.rating
+            .map_or(1.0, |stars| RATING_FACTORS[usize::from(stars.clamp(1, 5)) - 1])
     }
 
     /// Calculates the selection weight for this video entry.
-    /// The weight is inversely proportional to (pick_count + 1).
-    /// Videos picked fewer times have a higher weight.
+    /// The weight is inversely proportional to (pick_count + 1), times `weight_multiplier`
+    /// and `rating_factor`. Videos picked fewer times or rated higher have a higher weight.
     ///
     /// Examples:
     /// * `pick_count` = 0 => weight = 1.0 / (0 + 1) = 1.0
@@ -36,7 +78,37 @@ impl VideoEntry {
     pub fn weight(&self) -> f64 {
         // Adding 1.0 ensures unpicked items (pick_count = 0) have a weight of 1.0
         // and avoids division by zero (though pick_count is usize).
-        1.0 / (self.pick_count as f64 + 1.0)
+        self.weight_multiplier * self.rating_factor() / (self.pick_count as f64 + 1.0)
+    }
+
+    /// Calculates a selection weight where each past pick counts less the older it is.
+    /// A pick made `half_life` ago counts as half a pick, one made twice that long ago as
+    /// a quarter, and so on; the weight is then `1 / (decayed_count + 1)` as in `weight`.
+    /// Picks without a recorded time (beyond `picked_at`) count in full, and the result is
+    /// multiplied by `weight_multiplier` and `rating_factor`.
+    ///
+    /// Examples with a 30-day half-life:
+    /// * picked just now => weight ≈ 1.0 / (1.0 + 1) = 0.5
+    /// * picked 30 days ago => weight = 1.0 / (0.5 + 1) = 0.667
+    /// * picked 3 years ago => weight ≈ 1.0
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The time to measure the age of each pick against.
+    /// * `half_life` - How long it takes for a pick's penalty to halve.
+    pub fn decayed_weight(&self, now: DateTime<Utc>, half_life: Duration) -> f64 {
+        let half_life_secs = half_life.as_secs_f64().max(1.0);
+        let undated = self.pick_count.saturating_sub(self.picked_at.len()) as f64;
+        let decayed: f64 = self
+            .picked_at
+            .iter()
+            .map(|picked_at| {
+                // Picks stamped in the future (clock changes) count in full.
+                let age_secs = (now - *picked_at).num_seconds().max(0) as f64;
+                0.5f64.powf(age_secs / half_life_secs)
+            })
+            .sum();
+        self.weight_multiplier * self.rating_factor() / (undated + decayed + 1.0)
     }
 }
 
@@ -54,5 +126,66 @@ mod tests {
 
         let entry_3 = VideoEntry::new(PathBuf::from("video3.mp4"), 3);
         assert!((entry_3.weight() - 0.25).abs() < f64::EPSILON);
+
+        let mut favorite = VideoEntry::new(PathBuf::from("video4.mp4"), 1);
+        favorite.weight_multiplier = 3.0;
+        assert!((favorite.weight() - 1.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_rating_blends_into_weight() {
+        let rated = |stars: Option<u8>| {
+            let mut entry = VideoEntry::new(PathBuf::from("video.mp4"), 1);
+            entry.rating = stars;
+            entry.weight()
+        };
+        assert!((rated(None) - 0.5).abs() < f64::EPSILON);
+        assert!((rated(Some(3)) - 0.5).abs() < f64::EPSILON);
+        assert!((rated(Some(5)) - 1.25).abs() < f64::EPSILON);
+        assert!((rated(Some(1)) - 0.05).abs() < f64::EPSILON);
+        // A 5-star video picked once comes back sooner than an unrated, unpicked one.
+        assert!(rated(Some(5)) > VideoEntry::new(PathBuf::from("new.mp4"), 0).weight());
+    }
+
+    #[test]
+    fn test_with_picks() {
+        let now = Utc::now();
+        let earlier = now - chrono::Duration::days(3);
+        let entry = VideoEntry::with_picks(PathBuf::from("video.mp4"), vec![earlier, now]);
+        assert_eq!(entry.pick_count, 2);
+        assert_eq!(entry.last_picked, Some(now));
+        assert_eq!(entry.picked_at, vec![now, earlier]);
+
+        let never = VideoEntry::with_picks(PathBuf::from("other.mp4"), Vec::new());
+        assert_eq!(never.pick_count, 0);
+        assert_eq!(never.last_picked, None);
+    }
+
+    #[test]
+    fn test_decayed_weight_calculation() {
+        let now = Utc::now();
+        let half_life = Duration::from_secs(30 * 86_400);
+        let weight_after = |days: i64| {
+            VideoEntry::with_picks(
+                PathBuf::from("video.mp4"),
+                vec![now - chrono::Duration::days(days)],
+            )
+            .decayed_weight(now, half_life)
+        };
+
+        assert!((weight_after(0) - 0.5).abs() < 1e-9);
+        assert!((weight_after(30) - 1.0 / 1.5).abs() < 1e-9);
+        assert!(weight_after(3 * 365) > 0.999);
+
+        // Five picks three years ago are penalised less than one pick yesterday.
+        let old_favorite = VideoEntry::with_picks(
+            PathBuf::from("old.mp4"),
+            vec![now - chrono::Duration::days(3 * 365); 5],
+        );
+        assert!(old_favorite.decayed_weight(now, half_life) > weight_after(1));
+
+        // Counts without timestamps are not decayed.
+        let undated = VideoEntry::new(PathBuf::from("undated.mp4"), 1);
+        assert!((undated.decayed_weight(now, half_life) - 0.5).abs() < f64::EPSILON);
     }
 }
@@ -0,0 +1,156 @@
+// src/video_sniffer.rs
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+/// Size of an MPEG transport stream packet.
+const TS_PACKET_SIZE: usize = 188;
+/// Size of a Blu-ray (M2TS) packet: a 4-byte timestamp followed by a TS packet.
+const M2TS_PACKET_SIZE: usize = 192;
+/// Number of consecutive sync bytes required before a file is treated as a transport stream.
+const TS_PACKETS_TO_CHECK: usize = 3;
+/// Bytes read from the start of a file; enough for every signature below.
+const SNIFF_LEN: usize = M2TS_PACKET_SIZE * TS_PACKETS_TO_CHECK;
+/// Major brands of ISO base media files that hold still images (HEIF/HEIC, AVIF) or audio
+/// only (M4A, M4B, M4P and their Flash variants) rather than video.
+const NON_VIDEO_BRANDS: &[&[u8; 4]] = &[
+    b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"mif1", b"msf1", b"avif", b"avis",
+    b"M4A ", b"M4B ", b"M4P ", b"F4A ", b"F4B ",
+];
+
+/// Video container formats recognized by their leading bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoContainer {
+    /// ISO base media (MP4, MOV, M4V, 3GP): `ftyp` box at offset 4, with a major brand
+    /// other than the image and audio ones in `NON_VIDEO_BRANDS`.
+    Mp4,
+    /// Matroska / WebM: EBML header `1A 45 DF A3`.
+    Matroska,
+    /// AVI: `RIFF` header with `AVI ` form type.
+    Avi,
+    /// MPEG transport stream (TS or M2TS): repeated `0x47` sync bytes.
+    MpegTs,
+    /// MPEG program stream (MPG, VOB): pack start code `00 00 01 BA`.
+    MpegPs,
+}
+
+/// Returns true if a sync byte (0x47) appears at `offset` in each of the first packets.
+fn has_ts_sync(header: &[u8], packet_size: usize, offset: usize) -> bool {
+    (0..TS_PACKETS_TO_CHECK).all(|i| header.get(offset + i * packet_size) == Some(&0x47))
+}
+
+/// Identifies the container format from the first bytes of a file.
+pub fn detect_container(header: &[u8]) -> Option<VideoContainer> {
+    if header.get(4..8) == Some(b"ftyp") {
+        let brand = header.get(8..12)?;
+        if NON_VIDEO_BRANDS
+            .iter()
+            .any(|non_video| &non_video[..] == brand)
+        {
+            None
+        } else {
+            Some(VideoContainer::Mp4)
+        }
+    } else if header.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        Some(VideoContainer::Matroska)
+    } else if header.starts_with(b"RIFF") && header.get(8..12) == Some(b"AVI ") {
+        Some(VideoContainer::Avi)
+    } else if has_ts_sync(header, TS_PACKET_SIZE, 0) || has_ts_sync(header, M2TS_PACKET_SIZE, 4) {
+        Some(VideoContainer::MpegTs)
+    } else if header.starts_with(&[0x00, 0x00, 0x01, 0xBA]) {
+        Some(VideoContainer::MpegPs)
+    } else {
+        None
+    }
+}
+
+/// Reads the start of the file at `path` and identifies its container format.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened or read.
+pub fn sniff_video_container(path: &Path) -> Result<Option<VideoContainer>, io::Error> {
+    let mut header = Vec::with_capacity(SNIFF_LEN);
+    File::open(path)?
+        .take(SNIFF_LEN as u64)
+        .read_to_end(&mut header)?;
+    Ok(detect_container(&header))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_detect_container_signatures() {
+        let mut mp4 = vec![0x00, 0x00, 0x00, 0x20];
+        mp4.extend_from_slice(b"ftypisom");
+        assert_eq!(detect_container(&mp4), Some(VideoContainer::Mp4));
+
+        let mkv = [0x1A, 0x45, 0xDF, 0xA3, 0x01, 0x00];
+        assert_eq!(detect_container(&mkv), Some(VideoContainer::Matroska));
+
+        let mut avi = b"RIFF".to_vec();
+        avi.extend_from_slice(&[0x10, 0x00, 0x00, 0x00]);
+        avi.extend_from_slice(b"AVI LIST");
+        assert_eq!(detect_container(&avi), Some(VideoContainer::Avi));
+
+        let mut wav = b"RIFF".to_vec();
+        wav.extend_from_slice(&[0x10, 0x00, 0x00, 0x00]);
+        wav.extend_from_slice(b"WAVEfmt ");
+        assert_eq!(detect_container(&wav), None);
+
+        let ps = [0x00, 0x00, 0x01, 0xBA, 0x44];
+        assert_eq!(detect_container(&ps), Some(VideoContainer::MpegPs));
+
+        assert_eq!(detect_container(b"plain text"), None);
+        assert_eq!(detect_container(&[]), None);
+    }
+
+    #[test]
+    fn test_detect_container_rejects_image_and_audio_brands() {
+        let mut heic = vec![0x00, 0x00, 0x00, 0x18];
+        heic.extend_from_slice(b"ftypheic\x00\x00\x00\x00mif1heic");
+        assert_eq!(detect_container(&heic), None);
+
+        let mut m4a = vec![0x00, 0x00, 0x00, 0x20];
+        m4a.extend_from_slice(b"ftypM4A \x00\x00\x02\x00M4A isom");
+        assert_eq!(detect_container(&m4a), None);
+
+        let mut m4v = vec![0x00, 0x00, 0x00, 0x1C];
+        m4v.extend_from_slice(b"ftypM4V \x00\x00\x00\x01M4V M4A ");
+        assert_eq!(detect_container(&m4v), Some(VideoContainer::Mp4));
+    }
+
+    #[test]
+    fn test_detect_transport_streams() {
+        let mut ts = vec![0u8; TS_PACKET_SIZE * TS_PACKETS_TO_CHECK];
+        for i in 0..TS_PACKETS_TO_CHECK {
+            ts[i * TS_PACKET_SIZE] = 0x47;
+        }
+        assert_eq!(detect_container(&ts), Some(VideoContainer::MpegTs));
+
+        let mut m2ts = vec![0u8; SNIFF_LEN];
+        for i in 0..TS_PACKETS_TO_CHECK {
+            m2ts[4 + i * M2TS_PACKET_SIZE] = 0x47;
+        }
+        assert_eq!(detect_container(&m2ts), Some(VideoContainer::MpegTs));
+
+        // A single 0x47 byte (e.g. a text file starting with 'G') is not enough.
+        assert_eq!(detect_container(b"GIF89a"), None);
+    }
+
+    #[test]
+    fn test_sniff_video_container_reads_file() {
+        let file = NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), [0x1A, 0x45, 0xDF, 0xA3, 0x42, 0x86]).unwrap();
+        assert_eq!(
+            sniff_video_container(file.path()).unwrap(),
+            Some(VideoContainer::Matroska)
+        );
+    }
+}